@@ -1,10 +1,18 @@
 use serde_json::Value;
 
+pub mod multipart;
+
+pub use multipart::{Multipart, UploadedFile};
+
 #[derive(Clone)]
 pub struct Body {
     pub json: Option<Value>,
     pub url_encoded: Option<Vec<(String, String)>>,
+    pub multipart: Option<Multipart>,
     pub raw: Option<String>,
+    // The body exactly as received, after any `Content-Encoding` is undone.
+    // Empty for bodies `MultipartParser` parsed straight off the socket.
+    pub bytes: Vec<u8>,
 }
 
 impl Body {
@@ -12,7 +20,29 @@ impl Body {
         Self {
             json: None,
             url_encoded: None,
+            multipart: None,
+            bytes: raw.clone().into_bytes(),
             raw: Some(raw),
         }
     }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            json: None,
+            url_encoded: None,
+            multipart: None,
+            raw: Some(String::from_utf8_lossy(&bytes).into_owned()),
+            bytes,
+        }
+    }
+
+    pub fn from_multipart(multipart: Multipart) -> Self {
+        Self {
+            json: None,
+            url_encoded: None,
+            multipart: Some(multipart),
+            raw: None,
+            bytes: Vec::new(),
+        }
+    }
 }
@@ -0,0 +1,90 @@
+use crate::random;
+use std::fs::{self, File, OpenOptions};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// A `multipart/form-data` body, as parsed by `MultipartParser`.
+#[derive(Debug, Clone, Default)]
+pub struct Multipart {
+    pub fields: Vec<(String, String)>,
+    pub files: Vec<UploadedFile>,
+}
+
+impl Multipart {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn file(&self, name: &str) -> Option<&UploadedFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+    // The form field the file was sent under.
+    pub name: String,
+    // As the client sent it, possibly empty. Never use it as a path as is.
+    pub filename: String,
+    pub content_type: String,
+    pub size: u64,
+    pub(crate) contents: Contents,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Contents {
+    Memory(Vec<u8>),
+    Disk(Arc<TempFile>),
+}
+
+impl UploadedFile {
+    // The temporary file holding the upload, for parts too large to keep in
+    // memory. It is removed once the request is dropped.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.contents {
+            Contents::Memory(_) => None,
+            Contents::Disk(file) => Some(&file.0),
+        }
+    }
+
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        match &self.contents {
+            Contents::Memory(bytes) => Ok(bytes.clone()),
+            Contents::Disk(file) => fs::read(&file.0),
+        }
+    }
+
+    // Copies the upload to `path`, wherever it is kept.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        match &self.contents {
+            Contents::Memory(bytes) => fs::write(path, bytes),
+            Contents::Disk(file) => fs::copy(&file.0, path).map(|_| ()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TempFile(PathBuf);
+
+impl TempFile {
+    // Creates an empty file in `dir`, returned open for writing.
+    pub(crate) fn create(dir: &Path) -> Result<(Self, File)> {
+        let path = dir.join(format!("just_serve_it-{}.upload", random::hex(16)?));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok((Self(path), file))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
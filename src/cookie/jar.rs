@@ -0,0 +1,29 @@
+use super::Keys;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// The cookies sent with a request, along with the server's keys so signed
+// and private values can be checked.
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    cookies: HashMap<String, String>,
+    keys: Option<Arc<Keys>>,
+}
+
+impl CookieJar {
+    pub fn new(cookies: HashMap<String, String>, keys: Option<Arc<Keys>>) -> Self {
+        Self { cookies, keys }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    pub fn get_signed(&self, name: &str) -> Option<String> {
+        self.keys.as_ref()?.verify(name, self.get(name)?)
+    }
+
+    pub fn get_private(&self, name: &str) -> Option<String> {
+        self.keys.as_ref()?.decrypt(name, self.get(name)?)
+    }
+}
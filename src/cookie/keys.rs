@@ -0,0 +1,147 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::io::{Error, ErrorKind, Result};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNATURE_LEN: usize = 43;
+const NONCE_LEN: usize = 12;
+const MIN_MASTER_LEN: usize = 32;
+
+#[derive(Clone)]
+pub struct Key {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl Key {
+    // Derives independent signing and encryption keys from `master`, which
+    // must be at least 32 bytes of secret random data.
+    pub fn derive_from(master: &[u8]) -> Result<Self> {
+        if master.len() < MIN_MASTER_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("cookie master key must be at least {MIN_MASTER_LEN} bytes"),
+            ));
+        }
+
+        Ok(Self {
+            signing: derive(master, b"just_serve_it cookie signing"),
+            encryption: derive(master, b"just_serve_it cookie encryption"),
+        })
+    }
+
+    pub fn generate() -> Result<Self> {
+        let mut master = [0; 64];
+        getrandom::getrandom(&mut master).map_err(|err| Error::other(err.to_string()))?;
+
+        Self::derive_from(&master)
+    }
+
+    fn sign(&self, name: &str, value: &str) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.signing)
+            .expect("HMAC accepts keys of any length");
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(value.as_bytes());
+        mac
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&self.encryption.into())
+    }
+}
+
+// Cookies are always signed or encrypted with the current key; values made
+// with one of the previous keys are still accepted so keys can be rotated
+// without logging everyone out.
+#[derive(Clone)]
+pub struct Keys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+impl Keys {
+    pub fn new(current: Key) -> Self {
+        Self {
+            current,
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn accept(mut self, previous: Key) -> Self {
+        self.previous.push(previous);
+        self
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.current).chain(&self.previous)
+    }
+
+    pub fn sign(&self, name: &str, value: &str) -> String {
+        let tag = self.current.sign(name, value).finalize().into_bytes();
+        format!("{}{}", URL_SAFE_NO_PAD.encode(tag), value)
+    }
+
+    pub fn verify(&self, name: &str, signed: &str) -> Option<String> {
+        if !signed.is_char_boundary(SIGNATURE_LEN) {
+            return None;
+        }
+
+        let (tag, value) = signed.split_at(SIGNATURE_LEN);
+        let tag = URL_SAFE_NO_PAD.decode(tag).ok()?;
+
+        self.all()
+            .any(|key| key.sign(name, value).verify_slice(&tag).is_ok())
+            .then(|| value.to_string())
+    }
+
+    pub fn encrypt(&self, name: &str, value: &str) -> Result<String> {
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::getrandom(&mut nonce).map_err(|err| Error::other(err.to_string()))?;
+
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: name.as_bytes(),
+        };
+        let sealed = self
+            .current
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| Error::other("failed to encrypt cookie"))?;
+
+        let mut data = nonce.to_vec();
+        data.extend(sealed);
+
+        Ok(URL_SAFE_NO_PAD.encode(data))
+    }
+
+    pub fn decrypt(&self, name: &str, encrypted: &str) -> Option<String> {
+        let data = URL_SAFE_NO_PAD.decode(encrypted).ok()?;
+        if data.len() < NONCE_LEN {
+            return None;
+        }
+
+        let (nonce, sealed) = data.split_at(NONCE_LEN);
+        let plain = self.all().find_map(|key| {
+            let payload = Payload {
+                msg: sealed,
+                aad: name.as_bytes(),
+            };
+            key.cipher().decrypt(Nonce::from_slice(nonce), payload).ok()
+        })?;
+
+        String::from_utf8(plain).ok()
+    }
+}
+
+fn derive(master: &[u8], label: &[u8]) -> [u8; 32] {
+    let mut mac =
+        <HmacSha256 as Mac>::new_from_slice(master).expect("HMAC accepts keys of any length");
+    mac.update(label);
+    mac.finalize().into_bytes().into()
+}
@@ -0,0 +1,216 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// A file bundled into the binary by `generate`.
+#[derive(Debug)]
+pub struct Asset {
+    // The path under the bundled directory, starting with `/`.
+    pub path: &'static str,
+    pub contents: &'static [u8],
+    // Seconds since the Unix epoch, used for `Last-Modified` and the ETag.
+    pub modified: u64,
+}
+
+// Writes Rust code for a `&[Asset]` holding every file under `dir` to
+// `out`. Meant for build scripts:
+//
+//     // build.rs
+//     let out = Path::new(&env::var("OUT_DIR").unwrap()).join("assets.rs");
+//     just_serve_it::embed::generate("public", &out).unwrap();
+//
+//     // main.rs
+//     static ASSETS: &[Asset] = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+//     server._use_(ServeStatic::embedded(ASSETS));
+//
+// It also tells Cargo to run the build script again when the directory
+// changes. Files and directories whose name starts with a dot (`.env`,
+// `.git`, editor swap files) are left out, and so are links to
+// directories, which could otherwise loop, and links to files outside
+// `dir`.
+pub fn generate(dir: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<()> {
+    write(dir.as_ref(), out.as_ref(), false)
+}
+
+// Like `generate`, but bundles dotfiles too.
+pub fn generate_with_dotfiles(dir: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<()> {
+    write(dir.as_ref(), out.as_ref(), true)
+}
+
+fn write(dir: &Path, out: &Path, dotfiles: bool) -> Result<()> {
+    let dir = fs::canonicalize(dir)?;
+    let mut files = Vec::new();
+    collect(&dir, &dir, "", dotfiles, &mut files)?;
+    files.sort();
+
+    let mut code = String::from("&[\n");
+    for (path, file) in &files {
+        let modified = fs::metadata(file)?
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_secs());
+
+        let _ = writeln!(
+            code,
+            "    just_serve_it::embed::Asset {{ path: {path:?}, contents: include_bytes!({:?}), modified: {modified} }},",
+            file.to_string_lossy(),
+        );
+    }
+    code.push_str("]\n");
+
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    fs::write(out, code)
+}
+
+fn collect(
+    root: &Path,
+    dir: &Path,
+    prefix: &str,
+    dotfiles: bool,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{prefix}/{name}");
+
+        if name.starts_with('.') && !dotfiles {
+            continue;
+        }
+
+        let file_type = fs::symlink_metadata(entry.path())?.file_type();
+        if file_type.is_dir() {
+            collect(root, &entry.path(), &path, dotfiles, files)?;
+        } else if file_type.is_file() {
+            files.push((path, entry.path()));
+        } else if file_type.is_symlink() {
+            // Links to files inside `root` are bundled like the file itself,
+            // unless they lead to a dotfile that would be left out. Links
+            // out of `root`, to directories and dangling links are skipped.
+            let target = match fs::canonicalize(entry.path()) {
+                Ok(target) if target.is_file() => target,
+                _ => continue,
+            };
+            let hidden = match target.strip_prefix(root) {
+                Ok(relative) => relative
+                    .components()
+                    .any(|part| part.as_os_str().to_string_lossy().starts_with('.')),
+                Err(_) => continue,
+            };
+            if dotfiles || !hidden {
+                files.push((path, target));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::ServeStatic;
+    use crate::routes::Router;
+    use crate::testing::{fixture, get};
+
+    fn generate(dir: &Path, dotfiles: bool) -> String {
+        let out = dir.join(if dotfiles { "all.rs" } else { "assets.rs" });
+        write(&dir.join("public"), &out, dotfiles).unwrap();
+        fs::read_to_string(out).unwrap()
+    }
+
+    fn paths(code: &str) -> Vec<&str> {
+        code.lines()
+            .filter_map(|line| line.split("path: \"").nth(1))
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect()
+    }
+
+    // What `include!` would make of the generated code: each entry's path
+    // and the contents `include_bytes!` would pull in.
+    fn assets(code: &str) -> &'static [Asset] {
+        let field = |line: &str, start: &str| {
+            let rest = &line[line.find(start).unwrap() + start.len()..];
+            rest[..rest.find('"').unwrap()].to_string()
+        };
+
+        let assets: Vec<Asset> = code
+            .lines()
+            .filter(|line| line.contains("Asset {"))
+            .map(|line| Asset {
+                path: Box::leak(field(line, "path: \"").into_boxed_str()),
+                contents: Box::leak(
+                    fs::read(field(line, "include_bytes!(\""))
+                        .unwrap()
+                        .into_boxed_slice(),
+                ),
+                modified: 0,
+            })
+            .collect();
+
+        Box::leak(assets.into_boxed_slice())
+    }
+
+    #[test]
+    fn skips_dotfiles_and_directory_links() {
+        let dir = fixture("embed-walk");
+
+        let mut expected = vec!["/a.txt", "/docs/guide.txt", "/index.html"];
+        if cfg!(unix) {
+            expected.push("/inside");
+        }
+        assert_eq!(paths(&generate(&dir, false)), expected);
+
+        let all = generate(&dir, true);
+        let all = paths(&all);
+        for path in ["/.env", "/.git/config"] {
+            assert!(all.contains(&path), "{path}");
+        }
+        if cfg!(unix) {
+            assert!(all.contains(&"/env"));
+        }
+        assert!(!all.iter().any(|path| path.starts_with("/loop")));
+        assert!(!all.iter().any(|path| path.starts_with("/git")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_links_out_of_the_directory() {
+        let dir = fixture("embed-outside");
+
+        let code = generate(&dir, true);
+        assert!(!paths(&code).contains(&"/outside"));
+        assert!(!code.contains("secret.txt"));
+        // Links inside are bundled from their target.
+        assert!(code.contains("docs/guide.txt"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn serves_generated_assets() {
+        let dir = fixture("embed-serve");
+        let serve = ServeStatic::embedded(assets(&generate(&dir, false)));
+        let mut routes = Router::new();
+
+        let sent = get(&serve, &mut routes, "/", &[]).unwrap();
+        assert_eq!(sent.status, 200);
+        assert_eq!(sent.body, b"<h1>home</h1>");
+
+        let sent = get(&serve, &mut routes, "/docs/guide.txt", &[]).unwrap();
+        assert_eq!(sent.status, 200);
+        assert_eq!(sent.body, b"guide");
+
+        for path in ["/.env", "/.git/config", "/loop/index.html", "/outside"] {
+            assert!(get(&serve, &mut routes, path, &[]).is_none(), "{path}");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+use super::header::Header;
+use super::request::Request;
+use super::response::Response;
+use serde::de::DeserializeOwned;
+use serde_json::error::Category;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Result;
+use std::ops::{Deref, DerefMut};
+
+mod de;
+
+pub(crate) type StateMap = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+// Builds a handler argument from the incoming request. Failing extractors
+// answer the request with their rejection instead of calling the handler.
+pub trait FromRequest: Sized {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub status_code: u16,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(status_code: u16, message: impl fmt::Display) -> Self {
+        Self {
+            status_code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn send(self, mut res: Response) -> Result<()> {
+        res.status(self.status_code).text(&self.message)
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.status_code, self.message)
+    }
+}
+
+impl FromRequest for Request {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        Ok(req.clone())
+    }
+}
+
+macro_rules! impl_deref {
+    ($($name:ident),*) => {
+        $(
+            impl<T> Deref for $name<T> {
+                type Target = T;
+
+                fn deref(&self) -> &T {
+                    &self.0
+                }
+            }
+
+            impl<T> DerefMut for $name<T> {
+                fn deref_mut(&mut self) -> &mut T {
+                    &mut self.0
+                }
+            }
+        )*
+    };
+}
+
+impl_deref!(Json, Form, Query, Path, State);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        match req.content_type() {
+            Some(content_type)
+                if content_type.is("application/json") || content_type.mime.ends_with("+json") => {}
+            _ => return Err(Rejection::new(415, "Expected a JSON request body")),
+        }
+
+        let raw = raw_body(req);
+        serde_json::from_str(raw).map(Json).map_err(|err| {
+            let status_code = match err.classify() {
+                Category::Data => 422,
+                Category::Syntax | Category::Eof | Category::Io => 400,
+            };
+            Rejection::new(status_code, format!("Invalid JSON body: {err}"))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Form<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Form<T> {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        match req.content_type() {
+            Some(content_type) if content_type.is("application/x-www-form-urlencoded") => {}
+            _ => return Err(Rejection::new(415, "Expected a URL-encoded form body")),
+        }
+
+        serde_urlencoded::from_str(raw_body(req))
+            .map(Form)
+            .map_err(|err| Rejection::new(422, format!("Invalid form body: {err}")))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        serde_urlencoded::from_str(req.query.as_deref().unwrap_or(""))
+            .map(Query)
+            .map_err(|err| Rejection::new(400, format!("Invalid query string: {err}")))
+    }
+}
+
+// The parameters captured by `:name` segments of the route. Deserializes into
+// a single value, a tuple in route order or a struct keyed by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest for Path<T> {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        T::deserialize(de::Params(&req.params))
+            .map(Path)
+            .map_err(|err| Rejection::new(400, format!("Invalid path parameters: {err}")))
+    }
+}
+
+// Shared application state registered with `Server::state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State<T>(pub T);
+
+impl<T: Clone + Send + Sync + 'static> FromRequest for State<T> {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        req.state
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .map(|value| State(value.clone()))
+            .ok_or_else(|| {
+                Rejection::new(
+                    500,
+                    format!("No state of type {} registered", std::any::type_name::<T>()),
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Headers(pub Header);
+
+impl FromRequest for Headers {
+    fn from_request(req: &Request) -> std::result::Result<Self, Rejection> {
+        Ok(Headers(req.headers.clone()))
+    }
+}
+
+fn raw_body(req: &Request) -> &str {
+    req.body
+        .as_ref()
+        .and_then(|body| body.raw.as_deref())
+        .unwrap_or("")
+}
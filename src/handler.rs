@@ -0,0 +1,81 @@
+use super::extract::FromRequest;
+use super::request::Request;
+use super::response::{IntoResponse, Response};
+use std::io::Result;
+use std::sync::Arc;
+
+pub type Callback = Arc<dyn Fn(Request, Response) -> Result<()> + Send + Sync>;
+
+// Implemented for functions taking any number of extractors followed by the
+// `Response`, e.g. `fn(Json<User>, Response) -> Result<()>`. `Request` is an
+// extractor too, so the classic `fn(Request, Response)` handlers still work.
+// Functions taking only extractors may instead return anything implementing
+// `IntoResponse`, and the server sends it for them.
+pub trait Handler<T>: Send + Sync + 'static {
+    fn call(&self, req: Request, res: Response) -> Result<()>;
+}
+
+macro_rules! impl_handler {
+    ($($ty:ident),*) => {
+        #[allow(non_snake_case, unused_variables, unused_mut)]
+        impl<F, $($ty,)*> Handler<($($ty,)*)> for F
+        where
+            F: Fn($($ty,)* Response) -> Result<()> + Send + Sync + 'static,
+            $($ty: FromRequest,)*
+        {
+            fn call(&self, req: Request, mut res: Response) -> Result<()> {
+                $(
+                    let $ty = match $ty::from_request(&req) {
+                        Ok(value) => value,
+                        Err(rejection) => return rejection.send(res),
+                    };
+                )*
+
+                self($($ty,)* res)
+            }
+        }
+    };
+}
+
+macro_rules! impl_returning_handler {
+    ($($ty:ident),*) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<F, R, $($ty,)*> Handler<fn($($ty,)*) -> R> for F
+        where
+            F: Fn($($ty,)*) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($ty: FromRequest,)*
+        {
+            fn call(&self, req: Request, res: Response) -> Result<()> {
+                $(
+                    let $ty = match $ty::from_request(&req) {
+                        Ok(value) => value,
+                        Err(rejection) => return rejection.send(res),
+                    };
+                )*
+
+                self($($ty,)*).into_response(res)
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(T1);
+impl_handler!(T1, T2);
+impl_handler!(T1, T2, T3);
+impl_handler!(T1, T2, T3, T4);
+impl_handler!(T1, T2, T3, T4, T5);
+impl_handler!(T1, T2, T3, T4, T5, T6);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+impl_returning_handler!();
+impl_returning_handler!(T1);
+impl_returning_handler!(T1, T2);
+impl_returning_handler!(T1, T2, T3);
+impl_returning_handler!(T1, T2, T3, T4);
+impl_returning_handler!(T1, T2, T3, T4, T5);
+impl_returning_handler!(T1, T2, T3, T4, T5, T6);
+impl_returning_handler!(T1, T2, T3, T4, T5, T6, T7);
+impl_returning_handler!(T1, T2, T3, T4, T5, T6, T7, T8);
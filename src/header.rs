@@ -1,14 +1,25 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::time::SystemTime;
 
-#[derive(Debug, Clone)]
+pub mod typed;
+
+pub use typed::{
+    Authorization, ByteRange, ContentDisposition, ContentType, EntityTag, Host, IfMatch,
+    IfNoneMatch, QualityItem, Range,
+};
+
+#[derive(Debug, Clone, Default)]
 pub struct Header {
     pub fields: HashMap<String, String>,
+    appended: Vec<(String, String)>,
 }
 
 impl Header {
     pub fn new() -> Self {
         Self {
             fields: HashMap::<String, String>::new(),
+            appended: Vec::new(),
         }
     }
 
@@ -16,27 +27,132 @@ impl Header {
         let mut fields = HashMap::<String, String>::new();
 
         for header in headers {
-            let x: Vec<&str> = header.split(": ").collect();
-            fields.insert(x[0].to_string(), x[1].to_string());
+            if let Some((key, value)) = header.split_once(':') {
+                fields.insert(key.trim().to_string(), value.trim().to_string());
+            }
         }
 
-        Self { fields }
+        Self {
+            fields,
+            appended: Vec::new(),
+        }
     }
 
+    // Header names are case-insensitive, so fall back to a scan when the
+    // exact spelling isn't present.
     pub fn get(&self, key: &str) -> Option<&String> {
-        self.fields.get(key)
+        self.fields.get(key).or_else(|| {
+            self.fields
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value)
+        })
     }
 
     pub fn set(&mut self, key: &str, value: &str) {
+        self.appended
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(key));
         self.fields.insert(key.to_string(), value.to_string());
     }
 
-    pub fn to_string(&self) -> String {
-        let mut headers = String::new();
+    // Adds another line for `key` instead of replacing it, for fields such as
+    // `Set-Cookie` that can't be folded into a single value.
+    pub fn append(&mut self, key: &str, value: &str) {
+        if self.get(key).is_none() {
+            self.set(key, value);
+        } else {
+            self.appended.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&String> {
+        self.get(key)
+            .into_iter()
+            .chain(
+                self.appended
+                    .iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case(key))
+                    .map(|(_, value)| value),
+            )
+            .collect()
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.fields
+            .retain(|name, _| !name.eq_ignore_ascii_case(key));
+        self.appended
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(key));
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        ContentType::parse(self.get("Content-Type")?)
+    }
+
+    pub fn content_disposition(&self) -> Option<ContentDisposition> {
+        ContentDisposition::parse(self.get("Content-Disposition")?)
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.get("Content-Length")?.trim().parse().ok()
+    }
+
+    pub fn accept(&self) -> Vec<QualityItem> {
+        self.get("Accept")
+            .map(|value| QualityItem::parse_list(value))
+            .unwrap_or_default()
+    }
+
+    pub fn accept_encoding(&self) -> Vec<QualityItem> {
+        self.get("Accept-Encoding")
+            .map(|value| QualityItem::parse_list(value))
+            .unwrap_or_default()
+    }
+
+    pub fn authorization(&self) -> Option<Authorization> {
+        Authorization::parse(self.get("Authorization")?)
+    }
+
+    pub fn if_none_match(&self) -> Option<IfNoneMatch> {
+        IfNoneMatch::parse(self.get("If-None-Match")?)
+    }
+
+    pub fn if_modified_since(&self) -> Option<SystemTime> {
+        typed::parse_http_date(self.get("If-Modified-Since")?)
+    }
+
+    pub fn if_match(&self) -> Option<IfMatch> {
+        IfMatch::parse(self.get("If-Match")?)
+    }
+
+    pub fn if_unmodified_since(&self) -> Option<SystemTime> {
+        typed::parse_http_date(self.get("If-Unmodified-Since")?)
+    }
+
+    pub fn range(&self) -> Option<Range> {
+        Range::parse(self.get("Range")?)
+    }
+
+    pub fn cookie(&self) -> Vec<(String, String)> {
+        self.get("Cookie")
+            .map(|value| typed::parse_cookie_pairs(value))
+            .unwrap_or_default()
+    }
+
+    pub fn host(&self) -> Option<Host> {
+        Host::parse(self.get("Host")?)
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (key, value) in &self.fields {
-            headers.push_str(&format!("{key}: {value}\r\n"));
+            write!(f, "{key}: {value}\r\n")?;
+        }
+
+        for (key, value) in &self.appended {
+            write!(f, "{key}: {value}\r\n")?;
         }
 
-        headers
+        Ok(())
     }
 }
@@ -0,0 +1,509 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use std::fmt;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentType {
+    pub mime: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = split_unquoted(value, ';').into_iter();
+        let mime = parts.next()?.trim().to_ascii_lowercase();
+
+        if !is_media_type(&mime) {
+            return None;
+        }
+
+        Some(Self {
+            mime,
+            params: parts.filter_map(parse_param).collect(),
+        })
+    }
+
+    pub fn is(&self, mime: &str) -> bool {
+        self.mime.eq_ignore_ascii_case(mime)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        find_param(&self.params, name)
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentDisposition {
+    // `form-data`, `attachment` or `inline`, lowercased.
+    pub kind: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl ContentDisposition {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = split_unquoted(value, ';').into_iter();
+        let kind = parts.next()?.trim().to_ascii_lowercase();
+
+        if kind.is_empty() || kind.contains('=') {
+            return None;
+        }
+
+        Some(Self {
+            kind,
+            params: parts.filter_map(parse_param).collect(),
+        })
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        find_param(&self.params, name)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.param("name")
+    }
+
+    // Prefers the extended `filename*=UTF-8''...` form when it is present
+    // and decodes.
+    pub fn filename(&self) -> Option<String> {
+        let extended = self.param("filename*").and_then(|value| {
+            let (charset, rest) = value.split_once('\'')?;
+            let (_language, encoded) = rest.split_once('\'')?;
+
+            if !charset.eq_ignore_ascii_case("utf-8") {
+                return None;
+            }
+            percent_decode_str(encoded)
+                .decode_utf8()
+                .ok()
+                .map(|name| name.into_owned())
+        });
+
+        extended.or_else(|| self.param("filename").map(str::to_string))
+    }
+}
+
+// One entry of a weighted list such as `Accept` or `Accept-Encoding`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityItem {
+    pub value: String,
+    pub params: Vec<(String, String)>,
+    pub q: f32,
+}
+
+impl QualityItem {
+    // Parses a comma separated list and orders it by preference, keeping the
+    // client's order for entries of equal weight.
+    pub fn parse_list(value: &str) -> Vec<Self> {
+        let mut items: Vec<Self> = split_unquoted(value, ',')
+            .into_iter()
+            .filter_map(|item| {
+                let mut parts = split_unquoted(item, ';').into_iter();
+                let value = parts.next()?.trim().to_ascii_lowercase();
+
+                if value.is_empty() {
+                    return None;
+                }
+
+                let mut q = 1.0;
+                let mut params = Vec::new();
+                for (name, param) in parts.filter_map(parse_param) {
+                    if name == "q" {
+                        q = param
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|q| (0.0..=1.0).contains(q))?;
+                    } else {
+                        params.push((name, param));
+                    }
+                }
+
+                Some(Self { value, params, q })
+            })
+            .collect();
+
+        items.sort_by(|a, b| b.q.total_cmp(&a.q));
+        items
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        find_param(&self.params, name)
+    }
+
+    // Of the content codings `offers`, the one `accepted` (a parsed
+    // `Accept-Encoding`) weighs highest, ties going to the earlier offer.
+    // Codings the list doesn't name get the weight of `*`, if present.
+    pub fn negotiate_encoding<'a>(accepted: &[Self], offers: &[&'a str]) -> Option<&'a str> {
+        let weight = |coding: &str| {
+            accepted
+                .iter()
+                .find(|item| item.value == coding || (coding == "gzip" && item.value == "x-gzip"))
+                .or_else(|| accepted.iter().find(|item| item.value == "*"))
+                .map_or(0.0, |item| item.q)
+        };
+
+        let mut best: Option<(&str, f32)> = None;
+        for offer in offers {
+            let q = weight(offer);
+
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((offer, q));
+            }
+        }
+
+        best.map(|(offer, _)| offer)
+    }
+
+    // Whether this media range (`*/*`, `text/*`, `text/html`) covers `mime`.
+    pub fn matches(&self, mime: &str) -> bool {
+        let mime = mime.to_ascii_lowercase();
+        match self.value.as_str() {
+            "*/*" | "*" => true,
+            range => match range.strip_suffix("/*") {
+                Some(kind) => mime.split('/').next() == Some(kind),
+                None => range == mime,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Authorization {
+    pub scheme: String,
+    pub credentials: String,
+}
+
+impl Authorization {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (scheme, credentials) = value.split_once(' ').unwrap_or((value, ""));
+
+        if scheme.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            scheme: scheme.to_string(),
+            credentials: credentials.trim().to_string(),
+        })
+    }
+
+    pub fn is_scheme(&self, scheme: &str) -> bool {
+        self.scheme.eq_ignore_ascii_case(scheme)
+    }
+
+    pub fn basic(&self) -> Option<(String, String)> {
+        if !self.is_scheme("Basic") {
+            return None;
+        }
+
+        let decoded = STANDARD.decode(&self.credentials).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+
+        Some((user.to_string(), password.to_string()))
+    }
+
+    pub fn bearer(&self) -> Option<&str> {
+        if self.is_scheme("Bearer") && !self.credentials.is_empty() {
+            Some(&self.credentials)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub weak: bool,
+    pub tag: String,
+}
+
+impl EntityTag {
+    pub fn strong(tag: &str) -> Self {
+        Self {
+            weak: false,
+            tag: tag.to_string(),
+        }
+    }
+
+    pub fn weak(tag: &str) -> Self {
+        Self {
+            weak: true,
+            tag: tag.to_string(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (weak, quoted) = match value.strip_prefix("W/") {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+
+        let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+        if tag.contains('"') {
+            return None;
+        }
+
+        Some(Self {
+            weak,
+            tag: tag.to_string(),
+        })
+    }
+
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/\"{}\"", self.tag)
+        } else {
+            write!(f, "\"{}\"", self.tag)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfNoneMatch {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl IfNoneMatch {
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.trim() == "*" {
+            return Some(Self::Any);
+        }
+
+        let tags = split_unquoted(value, ',')
+            .into_iter()
+            .map(EntityTag::parse)
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self::Tags(tags))
+    }
+
+    // `If-None-Match` uses the weak comparison function.
+    pub fn matches(&self, etag: &EntityTag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        }
+    }
+
+    // `If-Match` uses the strong one.
+    pub fn matches_strong(&self, etag: &EntityTag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.strong_eq(etag)),
+        }
+    }
+}
+
+// `If-Match` shares the grammar of `If-None-Match`.
+pub type IfMatch = IfNoneMatch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    FromTo(u64, u64),
+    From(u64),
+    Last(u64),
+}
+
+impl ByteRange {
+    // Resolves the range against a representation of `len` bytes, returning
+    // inclusive bounds, or `None` when it isn't satisfiable.
+    pub fn to_bounds(&self, len: u64) -> Option<(u64, u64)> {
+        if len == 0 {
+            return None;
+        }
+
+        match *self {
+            Self::FromTo(start, end) if start < len => Some((start, end.min(len - 1))),
+            Self::From(start) if start < len => Some((start, len - 1)),
+            Self::Last(count) if count > 0 => Some((len.saturating_sub(count), len - 1)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Range {
+    pub unit: String,
+    pub ranges: Vec<ByteRange>,
+}
+
+impl Range {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (unit, specs) = value.trim().split_once('=')?;
+
+        let ranges = specs
+            .split(',')
+            .map(|spec| {
+                let (start, end) = spec.trim().split_once('-')?;
+                let (start, end) = (start.trim(), end.trim());
+
+                match (start.is_empty(), end.is_empty()) {
+                    (true, false) => Some(ByteRange::Last(end.parse().ok()?)),
+                    (false, true) => Some(ByteRange::From(start.parse().ok()?)),
+                    (false, false) => {
+                        let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+                        (start <= end).then_some(ByteRange::FromTo(start, end))
+                    }
+                    (true, true) => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        if ranges.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            unit: unit.trim().to_ascii_lowercase(),
+            ranges,
+        })
+    }
+
+    pub fn is_bytes(&self) -> bool {
+        self.unit == "bytes"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub name: String,
+    pub port: Option<u16>,
+}
+
+impl Host {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        // IPv6 literals are bracketed: `[::1]:8080`
+        let (name, port) = if let Some(rest) = value.strip_prefix('[') {
+            let (name, rest) = rest.split_once(']')?;
+            if !rest.is_empty() && !rest.starts_with(':') {
+                return None;
+            }
+            (format!("[{name}]"), rest.strip_prefix(':'))
+        } else {
+            match value.rsplit_once(':') {
+                Some((name, port)) => (name.to_string(), Some(port)),
+                None => (value.to_string(), None),
+            }
+        };
+
+        if name.is_empty() {
+            return None;
+        }
+
+        let port = match port {
+            Some(port) => Some(port.parse().ok()?),
+            None => None,
+        };
+
+        Some(Self {
+            name: name.to_ascii_lowercase(),
+            port,
+        })
+    }
+}
+
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    httpdate::parse_http_date(value.trim()).ok()
+}
+
+pub fn fmt_http_date(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+pub fn parse_cookie_pairs(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let name = name.trim();
+
+            if name.is_empty() {
+                return None;
+            }
+
+            // Undoes the encoding `Cookie` applies to values.
+            let value = percent_decode_str(unquote(value.trim())).decode_utf8_lossy();
+            Some((name.to_string(), value.into_owned()))
+        })
+        .collect()
+}
+
+fn is_media_type(mime: &str) -> bool {
+    match mime.split_once('/') {
+        Some((kind, subtype)) => !kind.is_empty() && !subtype.is_empty() && !subtype.contains('/'),
+        None => false,
+    }
+}
+
+fn parse_param(param: &str) -> Option<(String, String)> {
+    let (name, value) = param.split_once('=')?;
+    let name = name.trim().to_ascii_lowercase();
+
+    if name.is_empty() {
+        return None;
+    }
+
+    Some((name, unquote(value.trim()).replace("\\\"", "\"")))
+}
+
+fn find_param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+// Splits on `separator` while leaving quoted strings intact.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == separator && !in_quotes => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+
+    parts
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .collect()
+}
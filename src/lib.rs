@@ -1,27 +1,52 @@
 pub mod body;
+pub mod cookie;
+pub mod embed;
+pub mod extract;
+pub mod handler;
 pub mod header;
 pub mod method;
 pub mod middleware;
+pub mod mime;
 pub mod path;
+mod random;
 pub mod request;
 pub mod response;
 pub mod routes;
+pub mod session;
 pub mod status;
+#[cfg(test)]
+mod testing;
 pub mod threadpool;
+pub mod version;
 
+use cookie::Keys;
+use extract::StateMap;
+use handler::{Callback, Handler};
 use method::Method;
 use middleware::Middleware;
-use request::Request;
+use mime::MimeTypes;
+use request::{Deadline, Request};
+use response::RedirectKind;
 use response::Response;
-use routes::{Route, Router};
-use std::io::{Read, Result};
+use routes::{Route, Router, TrailingSlash};
+use status::StatusCode;
+use std::any::TypeId;
+use std::io::{Error, ErrorKind, Result};
+use std::net::TcpStream;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use threadpool::ThreadPool;
 
 pub struct Server {
     name: String,
     middlewares: Vec<Arc<dyn Middleware>>,
     routes: Arc<Mutex<Router>>,
+    cookie_keys: Option<Arc<Keys>>,
+    mime_types: MimeTypes,
+    state: StateMap,
+    unsent_status: u16,
+    max_body_size: usize,
+    read_timeout: Duration,
 }
 
 impl Server {
@@ -30,9 +55,56 @@ impl Server {
             middlewares: Vec::new(),
             name: name.to_string(),
             routes: Arc::new(Mutex::new(Router::new())),
+            cookie_keys: None,
+            mime_types: MimeTypes::new(),
+            state: StateMap::new(),
+            unsent_status: 500,
+            max_body_size: 10 * 1024 * 1024,
+            read_timeout: Duration::from_secs(30),
         }
     }
 
+    // The status sent for handlers that finish without sending a response.
+    pub fn unsent_status(&mut self, status_code: StatusCode) {
+        self.unsent_status = status_code.as_u16();
+    }
+
+    // The largest request body accepted, 10 MiB by default. Compressed
+    // bodies are held to it both as sent and once decoded; larger ones are
+    // answered with 413 Content Too Large.
+    pub fn max_body_size(&mut self, bytes: usize) {
+        self.max_body_size = bytes;
+    }
+
+    // How long reading a whole request, body included, may take before it
+    // is answered with 408 Request Timeout, 30 seconds by default.
+    pub fn read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = timeout;
+    }
+
+    // Registers a value handlers can receive through the `State<T>` extractor.
+    pub fn state<T: Clone + Send + Sync + 'static>(&mut self, value: T) {
+        self.state.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub fn cookie_keys(&mut self, keys: Keys) {
+        self.cookie_keys = Some(Arc::new(keys));
+    }
+
+    // Registers or overrides the MIME type sent for files with `extension`.
+    pub fn mime_type(&mut self, extension: &str, mime: &str) {
+        self.mime_types.register(extension, mime);
+    }
+
+    // Guesses the type of files with unknown extensions from their content.
+    pub fn sniff_mime_types(&mut self, sniff: bool) {
+        self.mime_types.set_sniffing(sniff);
+    }
+
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) {
+        self.routes.lock().unwrap().trailing_slash = policy;
+    }
+
     #[allow(unused)]
     pub fn _use_(&mut self, middleware: impl Middleware + 'static) {
         self.middlewares.push(Arc::new(middleware));
@@ -44,66 +116,30 @@ impl Server {
             self.name, ip, port
         );
         let thread_pool = ThreadPool::new(4);
+        let context = Arc::new(Context {
+            middlewares: self.middlewares.clone(),
+            routes: self.routes.lock().unwrap().clone(),
+            cookie_keys: self.cookie_keys.clone(),
+            mime_types: Arc::new(self.mime_types.clone()),
+            state: Arc::new(std::mem::take(&mut self.state)),
+            unsent_status: self.unsent_status,
+            max_body_size: self.max_body_size,
+            read_timeout: self.read_timeout,
+        });
         let listener = std::net::TcpListener::bind(format!("{}:{}", ip, port))?;
 
         loop {
-            let (mut socket, _) = listener.accept()?;
-            let mut buf = [0; 1024];
-            let n = socket.read(&mut buf).unwrap();
-            let mut request_str = String::from_utf8_lossy(&buf[..n]);
-            let mut req = Request::parse(&mut request_str);
-            let mut res = Response::new(socket);
-
-            if req.method.is_none() {
-                res.status.status_code = 405;
-                res.send(format!("Method {:?} Not Allowed!", req.method).as_str())
-                    .unwrap();
-                continue;
-            }
-
-            let mut routes = self.routes.lock().unwrap();
-
-            // Handle Middlewares
-            for mw in &self.middlewares {
-                mw.handle(&mut routes, &mut req)?;
-            }
-
-            let req_path = req.path.as_ref().unwrap();
-            let route = routes.get(&req.method.as_ref().unwrap(), req_path);
-
-            fn not_found(req: Request, mut res: Response) -> Result<()> {
-                res.status.status_code = 404;
-                res.send(format!("Page {} Not Found!", req.path.unwrap()).as_str())
-                    .unwrap();
-                Ok(())
-            }
-
-            let callback: fn(Request, Response) -> Result<()>;
-
-            if let Some(route) = route {
-                callback = route.callback.unwrap();
-            } else {
-                let default_callback = routes.not_found;
-
-                if default_callback.is_some() {
-                    callback = default_callback.unwrap();
-                } else {
-                    callback = not_found;
-                }
-            }
+            let (socket, _) = listener.accept()?;
+            let context = Arc::clone(&context);
 
-            thread_pool.execute(move || {
-                callback(req, res).unwrap();
-            });
+            // Reading the request happens on the worker too, so a slow
+            // client only ever holds up its own.
+            thread_pool.execute(move || context.serve(socket));
         }
     }
 
     #[allow(unused)]
-    pub fn get(
-        &mut self,
-        path: &str,
-        _callback: fn(Request, Response) -> Result<()>,
-    ) -> Result<()> {
+    pub fn get<T>(&mut self, path: &str, _callback: impl Handler<T>) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
         routes.add(Route::new(path, Method::Get, _callback));
 
@@ -111,11 +147,7 @@ impl Server {
     }
 
     #[allow(unused)]
-    pub fn post(
-        &mut self,
-        path: &str,
-        _callback: fn(Request, Response) -> Result<()>,
-    ) -> Result<()> {
+    pub fn post<T>(&mut self, path: &str, _callback: impl Handler<T>) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
         routes.add(Route::new(path, Method::Post, _callback));
 
@@ -123,11 +155,7 @@ impl Server {
     }
 
     #[allow(unused)]
-    pub fn put(
-        &mut self,
-        path: &str,
-        _callback: fn(Request, Response) -> Result<()>,
-    ) -> Result<()> {
+    pub fn put<T>(&mut self, path: &str, _callback: impl Handler<T>) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
         routes.add(Route::new(path, Method::Put, _callback));
 
@@ -135,11 +163,7 @@ impl Server {
     }
 
     #[allow(unused)]
-    pub fn patch(
-        &mut self,
-        path: &str,
-        _callback: fn(Request, Response) -> Result<()>,
-    ) -> Result<()> {
+    pub fn patch<T>(&mut self, path: &str, _callback: impl Handler<T>) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
         routes.add(Route::new(path, Method::Patch, _callback));
 
@@ -147,11 +171,7 @@ impl Server {
     }
 
     #[allow(unused)]
-    pub fn delete(
-        &mut self,
-        path: &str,
-        _callback: fn(Request, Response) -> Result<()>,
-    ) -> Result<()> {
+    pub fn delete<T>(&mut self, path: &str, _callback: impl Handler<T>) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
         routes.add(Route::new(path, Method::Delete, _callback));
 
@@ -159,10 +179,167 @@ impl Server {
     }
 
     #[allow(unused)]
-    pub fn not_found(&mut self, _callback: fn(Request, Response) -> Result<()>) -> Result<()> {
+    pub fn not_found<T>(&mut self, _callback: impl Handler<T>) -> Result<()> {
         let mut routes = self.routes.lock().unwrap();
-        routes.not_found = Some(_callback);
+        routes.not_found = Some(Arc::new(move |req, res| _callback.call(req, res)));
 
         Ok(())
     }
 }
+
+// Everything a worker needs to answer a connection.
+struct Context {
+    middlewares: Vec<Arc<dyn Middleware>>,
+    routes: Router,
+    cookie_keys: Option<Arc<Keys>>,
+    mime_types: Arc<MimeTypes>,
+    state: Arc<StateMap>,
+    unsent_status: u16,
+    max_body_size: usize,
+    read_timeout: Duration,
+}
+
+impl Context {
+    fn serve(&self, socket: TcpStream) {
+        let reader = match socket.try_clone() {
+            Ok(reader) => Deadline::new(reader, self.read_timeout),
+            Err(err) => {
+                println!("ERROR: {:?}", err);
+                return;
+            }
+        };
+
+        let mut req = match Request::read(reader, self.max_body_size) {
+            Ok(req) => req,
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return,
+            Err(err) => return reject_unread(Response::new(socket), err),
+        };
+        let mut res = Response::new(socket);
+
+        req.state = Arc::clone(&self.state);
+        req.keys = self.cookie_keys.clone();
+        res.keys = self.cookie_keys.clone();
+        res.mime_types = Arc::clone(&self.mime_types);
+        res.unsent_status = self.unsent_status;
+        res.request_headers = req.headers.clone();
+        res.request_method = req.method.clone();
+
+        match req.version {
+            Some(version) => res.version = version,
+            None => {
+                res.status.status_code = 505;
+                if let Err(err) = res.send("HTTP Version Not Supported") {
+                    println!("ERROR: {:?}", err);
+                }
+                return;
+            }
+        }
+
+        if req.method.is_none() {
+            res.status.status_code = 405;
+            if let Err(err) = res.send(&format!("Method {:?} Not Allowed!", req.method)) {
+                println!("ERROR: {:?}", err);
+            }
+            return;
+        }
+
+        // Middleware may change the routes, but only for this request.
+        let mut routes = self.routes.clone();
+
+        // Handle Middlewares
+        for mw in &self.middlewares {
+            if let Err(err) = mw.handle(&mut routes, &mut req) {
+                return fail(res, err);
+            }
+        }
+
+        // Bodies no middleware streamed are read after all.
+        if let Err(err) = req.read_body(self.max_body_size) {
+            return reject_unread(res, err);
+        }
+
+        for mw in &self.middlewares {
+            if let Err(err) = mw.respond(&req, &mut res) {
+                return fail(res, err);
+            }
+        }
+
+        let method = req.method.clone().unwrap();
+        let req_path = req.path.clone().unwrap();
+        let mut route = routes.find(&method, &req_path);
+
+        if route.is_none() && req.handler.is_none() {
+            if let Some(alternate) = routes.alternate(&method, &req_path) {
+                match routes.trailing_slash {
+                    TrailingSlash::Strict => {}
+                    TrailingSlash::Redirect => {
+                        let location = match &req.query {
+                            Some(query) => format!("{alternate}?{query}"),
+                            None => alternate,
+                        };
+                        let kind = match method {
+                            Method::Get => RedirectKind::MovedPermanently,
+                            _ => RedirectKind::Permanent,
+                        };
+
+                        if let Err(err) = res.redirect(&location, kind) {
+                            println!("ERROR: {:?}", err);
+                        }
+                        return;
+                    }
+                    TrailingSlash::Ignore => route = routes.find(&method, &alternate),
+                }
+            }
+        }
+
+        fn not_found(req: Request, mut res: Response) -> Result<()> {
+            res.status.status_code = 404;
+            res.send(format!("Page {} Not Found!", req.path.unwrap()).as_str())
+                .unwrap();
+            Ok(())
+        }
+
+        let callback: Callback;
+
+        if let Some(handler) = req.handler.take() {
+            callback = handler;
+        } else if let Some((route, params)) = route {
+            callback = route.callback.clone().unwrap();
+            req.params = params;
+        } else {
+            let default_callback = routes.not_found.clone();
+
+            if let Some(default_callback) = default_callback {
+                callback = default_callback;
+            } else {
+                callback = Arc::new(not_found);
+            }
+        }
+
+        if let Err(err) = callback(req, res) {
+            println!("ERROR: {:?}", err);
+        }
+    }
+}
+
+// Answers a request middleware failed on.
+fn fail(mut res: Response, err: Error) {
+    println!("ERROR: {:?}", err);
+    res.status.status_code = 500;
+    if let Err(err) = res.send("Internal Server Error") {
+        println!("ERROR: {:?}", err);
+    }
+}
+
+// Answers a request whose head or body could not be read.
+fn reject_unread(mut res: Response, err: Error) {
+    res.status.status_code = match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => 408,
+        ErrorKind::FileTooLarge => 413,
+        ErrorKind::Unsupported => 415,
+        _ => 400,
+    };
+    if let Err(err) = res.send(&err.to_string()) {
+        println!("ERROR: {:?}", err);
+    }
+}
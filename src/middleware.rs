@@ -1,15 +1,44 @@
-use super::{request::Request, routes::Router};
+use super::{request::Request, response::Response, routes::Router};
+use crate::handler::Callback;
+use crate::status::Status;
 use std::io::Result;
+use std::sync::Arc;
 
 pub mod body_parser;
+pub mod compression;
 pub mod serve;
+pub mod session;
 
 #[allow(unused)]
-pub use body_parser::BodyParser;
+pub use body_parser::{BodyParser, MultipartParser};
 
 #[allow(unused)]
-pub use serve::ServeStatic;
+pub use compression::Compression;
+
+#[allow(unused)]
+pub use serve::{Dotfiles, FileCache, ServeStatic, Symlinks};
+
+#[allow(unused)]
+pub use session::Session;
 
 pub trait Middleware: Send + Sync {
     fn handle(&self, routes: &mut Router, req: &mut Request) -> Result<()>;
+
+    // Runs once every middleware has handled the request, before it is
+    // dispatched, so the response can be prepared (e.g. with hooks).
+    fn respond(&self, _req: &Request, _res: &mut Response) -> Result<()> {
+        Ok(())
+    }
+}
+
+// A handler answering with `status_code` and its reason phrase, for
+// middleware that turns a request away.
+pub(crate) fn reject(status_code: u16) -> Callback {
+    Arc::new(move |_, mut response: Response| {
+        response.status(status_code).text(&format!(
+            "{} {}",
+            status_code,
+            Status::phrase(status_code)
+        ))
+    })
 }
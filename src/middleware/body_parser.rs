@@ -1,7 +1,12 @@
-use super::Middleware;
+use super::{reject, Middleware};
+use crate::body::Body;
 use crate::{request::Request, routes::Router};
+use multipart::Limits;
 use serde_urlencoded;
-use std::io::Result;
+use std::io::{ErrorKind, Result};
+use std::path::PathBuf;
+
+mod multipart;
 
 enum DataTypes {
     Json,
@@ -30,8 +35,9 @@ impl BodyParser {
     }
 
     fn _json(&self, req: &mut Request) -> Result<()> {
-        if req.headers.get("Content-Type") != Some(&"application/json".to_string()) {
-            return Ok(());
+        match req.content_type() {
+            Some(content_type) if content_type.is("application/json") => {}
+            _ => return Ok(()),
         }
 
         if let Some(body) = &mut req.body {
@@ -40,8 +46,13 @@ impl BodyParser {
             }
 
             if let Some(data) = &body.raw {
-                let json_value = serde_json::from_str(data)?;
-                body.json = Some(json_value);
+                match serde_json::from_str(data) {
+                    Ok(value) => body.json = Some(value),
+                    Err(err) => {
+                        println!("ERROR: {:?}", err);
+                        req.handler = Some(reject(400));
+                    }
+                }
             }
         }
 
@@ -56,10 +67,8 @@ impl BodyParser {
     }
 
     fn _url_encoded(&self, req: &mut Request) -> Result<()> {
-        if let Some(content_type) = req.headers.get("Content-Type") {
-            let expected = "application/x-www-form-urlencoded".to_string();
-
-            if content_type == &expected {
+        if let Some(content_type) = req.content_type() {
+            if content_type.is("application/x-www-form-urlencoded") {
                 if let Some(body) = &mut req.body {
                     if body.url_encoded.is_some() {
                         return Ok(());
@@ -81,4 +90,134 @@ impl BodyParser {
 
         Ok(())
     }
+
+    // Parses `multipart/form-data` into `Body::multipart`, see
+    // `MultipartParser`.
+    #[allow(unused)]
+    pub fn multipart() -> MultipartParser {
+        MultipartParser {
+            limits: Limits::default(),
+        }
+    }
+}
+
+// Parses `multipart/form-data` bodies straight off the socket, so they
+// are never held in memory whole. Files larger than `in_memory_limit` are
+// written to temporary files as they arrive, removed again once the
+// request is dropped. Bodies breaking a limit are answered with 413
+// Content Too Large as soon as they do, malformed ones with 400 Bad
+// Request. `Server::max_body_size` doesn't apply to these bodies.
+pub struct MultipartParser {
+    limits: Limits,
+}
+
+impl Middleware for MultipartParser {
+    fn handle(&self, _routes: &mut Router, req: &mut Request) -> Result<()> {
+        let boundary = match req.content_type() {
+            Some(content_type) if content_type.is("multipart/form-data") => {
+                content_type.param("boundary").map(str::to_string)
+            }
+            _ => return Ok(()),
+        };
+        let boundary = match boundary {
+            Some(boundary) if (1..=70).contains(&boundary.len()) => boundary,
+            _ => {
+                req.body_reader = None;
+                req.handler = Some(reject(400));
+                return Ok(());
+            }
+        };
+
+        let parsed = match (req.body_reader.take(), &req.body) {
+            (Some(reader), _) => {
+                let mut reader = reader.lock().unwrap();
+                multipart::parse(&mut *reader, &boundary, &self.limits).map(Body::from_multipart)
+            }
+            // Already read, e.g. by an earlier parser.
+            (None, Some(body)) if body.multipart.is_none() => {
+                multipart::parse(&mut &body.bytes[..], &boundary, &self.limits).map(|parsed| {
+                    let mut body = body.clone();
+                    body.multipart = Some(parsed);
+                    body
+                })
+            }
+            _ => return Ok(()),
+        };
+
+        match parsed {
+            Ok(body) => req.body = Some(body),
+            Err(err) => {
+                println!("ERROR: {:?}", err);
+                req.handler = Some(reject(match err.kind() {
+                    ErrorKind::FileTooLarge => 413,
+                    ErrorKind::InvalidData => 400,
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut => 408,
+                    _ => 500,
+                }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MultipartParser {
+    // The largest single file accepted, 10 MiB by default.
+    pub fn file_size_limit(mut self, bytes: u64) -> Self {
+        self.limits.file_size = bytes;
+        self
+    }
+
+    // The largest body accepted, boundaries and part heads included,
+    // 10 MiB by default.
+    pub fn total_size_limit(mut self, bytes: u64) -> Self {
+        self.limits.total_size = bytes;
+        self
+    }
+
+    // Files up to this size, 64 KiB by default, are kept in memory.
+    pub fn in_memory_limit(mut self, bytes: usize) -> Self {
+        self.limits.memory = bytes;
+        self
+    }
+
+    // Where larger files are written, the system's temp directory by default.
+    pub fn temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.limits.temp_dir = Some(dir.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::Header;
+
+    fn request(content_type: &str, body: &str) -> Request {
+        let mut req = Request::new();
+        req.headers = Header::from(vec![format!("Content-Type: {content_type}")]);
+        req.body = Some(Body::new(body.to_string()));
+        req
+    }
+
+    #[test]
+    fn invalid_json_is_rejected() {
+        let parser = BodyParser::json();
+        let mut routes = Router::new();
+
+        let mut req = request("application/json", r#"{"a": [1, 2]}"#);
+        parser.handle(&mut routes, &mut req).unwrap();
+        assert_eq!(req.body.unwrap().json.unwrap()["a"][1], 2);
+        assert!(req.handler.is_none());
+
+        let mut req = request("application/json", "{1: 2}");
+        parser.handle(&mut routes, &mut req).unwrap();
+        assert!(req.body.unwrap().json.is_none());
+        assert!(req.handler.is_some());
+
+        // Other types are left alone.
+        let mut req = request("text/plain", "{1: 2}");
+        parser.handle(&mut routes, &mut req).unwrap();
+        assert!(req.handler.is_none());
+    }
 }
@@ -0,0 +1,360 @@
+use crate::body::multipart::{Contents, TempFile};
+use crate::body::{Multipart, UploadedFile};
+use crate::header::Header;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Boundary lines and part heads larger than this are rejected.
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+pub(super) struct Limits {
+    pub(super) file_size: u64,
+    pub(super) total_size: u64,
+    // File parts larger than this go to a temporary file.
+    pub(super) memory: usize,
+    pub(super) temp_dir: Option<PathBuf>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            file_size: 10 * 1024 * 1024,
+            total_size: 10 * 1024 * 1024,
+            memory: 64 * 1024,
+            temp_dir: None,
+        }
+    }
+}
+
+// Splits a body on `boundary` as it is read, writing large files to disk
+// chunk by chunk. Fails with `FileTooLarge` as soon as a limit is passed,
+// `InvalidData` for anything malformed and with the reader's own errors.
+pub(super) fn parse(body: &mut dyn Read, boundary: &str, limits: &Limits) -> Result<Multipart> {
+    let opening = format!("--{boundary}");
+    let delimiter = format!("\r\n--{boundary}");
+
+    let mut stream = Stream {
+        inner: body,
+        buf: Vec::new(),
+        read: 0,
+        limit: limits.total_size,
+    };
+    let mut multipart = Multipart::default();
+
+    // Anything before the first boundary is ignored.
+    stream.content(opening.as_bytes(), "missing boundary", |_| Ok(()))?;
+
+    // The stream always continues right after a boundary.
+    while !stream.starts_with(b"--")? {
+        stream.line("unterminated boundary")?;
+
+        let mut head = Vec::new();
+        let mut head_size = 0;
+        loop {
+            let line = stream.line("unterminated part head")?;
+            if line.is_empty() {
+                break;
+            }
+            head_size += line.len();
+            if head_size > MAX_HEAD_SIZE {
+                return Err(malformed("part head too large"));
+            }
+            head.push(String::from_utf8_lossy(&line).into_owned());
+        }
+
+        let headers = Header::from(head);
+        let disposition = headers
+            .content_disposition()
+            .filter(|disposition| disposition.kind == "form-data")
+            .ok_or_else(|| malformed("part without form-data disposition"))?;
+        let name = disposition
+            .name()
+            .ok_or_else(|| malformed("part without a name"))?
+            .to_string();
+
+        let filename = match disposition.filename() {
+            Some(filename) => filename,
+            None => {
+                let mut value = Vec::new();
+                stream.content(delimiter.as_bytes(), "missing closing boundary", |chunk| {
+                    value.extend_from_slice(chunk);
+                    Ok(())
+                })?;
+                let value = String::from_utf8_lossy(&value).into_owned();
+                multipart.fields.push((name, value));
+                continue;
+            }
+        };
+
+        let mut size = 0;
+        let mut memory = Vec::new();
+        let mut disk: Option<(TempFile, File)> = None;
+        stream.content(delimiter.as_bytes(), "missing closing boundary", |chunk| {
+            size += chunk.len() as u64;
+            if size > limits.file_size {
+                return Err(too_large("uploaded file exceeds the size limit"));
+            }
+
+            match &mut disk {
+                Some((_, file)) => file.write_all(chunk),
+                None if memory.len() + chunk.len() > limits.memory => {
+                    let dir = limits.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+                    // Owned from here on, so a failed write still cleans up.
+                    let (_, file) = disk.insert(TempFile::create(&dir)?);
+                    file.write_all(&memory)?;
+                    file.write_all(chunk)?;
+                    memory = Vec::new();
+                    Ok(())
+                }
+                None => {
+                    memory.extend_from_slice(chunk);
+                    Ok(())
+                }
+            }
+        })?;
+
+        let contents = match disk {
+            Some((temp, _)) => Contents::Disk(Arc::new(temp)),
+            None => Contents::Memory(memory),
+        };
+
+        multipart.files.push(UploadedFile {
+            name,
+            filename,
+            // The default for parts, per RFC 7578.
+            content_type: headers
+                .get("Content-Type")
+                .cloned()
+                .unwrap_or_else(|| "text/plain".to_string()),
+            size,
+            contents,
+        });
+    }
+
+    Ok(multipart)
+}
+
+// A body read in chunks, with what has been read but not yet consumed.
+struct Stream<'a> {
+    inner: &'a mut dyn Read,
+    buf: Vec<u8>,
+    read: u64,
+    limit: u64,
+}
+
+impl Stream<'_> {
+    // Reads another chunk into the buffer, false at the end of the body.
+    fn fill(&mut self) -> Result<bool> {
+        let mut chunk = [0; 8192];
+        let n = loop {
+            match self.inner.read(&mut chunk) {
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+
+        self.read += n as u64;
+        if self.read > self.limit {
+            return Err(too_large("multipart body exceeds the size limit"));
+        }
+
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(n > 0)
+    }
+
+    fn starts_with(&mut self, prefix: &[u8]) -> Result<bool> {
+        while self.buf.len() < prefix.len() && self.fill()? {}
+        Ok(self.buf.starts_with(prefix))
+    }
+
+    // Consumes a line and its CRLF, returning the line.
+    fn line(&mut self, missing: &str) -> Result<Vec<u8>> {
+        let mut from = 0;
+        loop {
+            if let Some(end) = find(&self.buf[from..], b"\r\n") {
+                let line = self.buf[..from + end].to_vec();
+                self.buf.drain(..from + end + 2);
+                return Ok(line);
+            }
+            if self.buf.len() > MAX_HEAD_SIZE {
+                return Err(malformed("line too long"));
+            }
+
+            // A CR at the end might be followed by its LF in the next chunk.
+            from = self.buf.len().saturating_sub(1);
+            if !self.fill()? {
+                return Err(malformed(missing));
+            }
+        }
+    }
+
+    // Passes everything up to `delimiter` to `sink` as it arrives, then
+    // consumes the delimiter.
+    fn content(
+        &mut self,
+        delimiter: &[u8],
+        missing: &str,
+        mut sink: impl FnMut(&[u8]) -> Result<()>,
+    ) -> Result<()> {
+        loop {
+            if let Some(end) = find(&self.buf, delimiter) {
+                sink(&self.buf[..end])?;
+                self.buf.drain(..end + delimiter.len());
+                return Ok(());
+            }
+
+            // The tail might be the start of a delimiter split across reads.
+            let keep = delimiter.len() - 1;
+            if self.buf.len() > keep {
+                let ready = self.buf.len() - keep;
+                sink(&self.buf[..ready])?;
+                self.buf.drain(..ready);
+            }
+
+            if !self.fill()? {
+                return Err(malformed(missing));
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn malformed(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn too_large(message: &str) -> Error {
+    Error::new(ErrorKind::FileTooLarge, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out the body one byte per read, so every delimiter and line
+    // ending is split across reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn body(file: &[u8]) -> Vec<u8> {
+        let mut body = b"preamble\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            Hello, --Xy world\r\n\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"upload\"; filename=\"a.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n"
+            .to_vec();
+        body.extend_from_slice(file);
+        body.extend_from_slice(b"\r\n--XyZ--\r\nepilogue");
+        body
+    }
+
+    fn parse_all(body: &[u8], limits: &Limits) -> Result<Multipart> {
+        parse(&mut Trickle(body), "XyZ", limits)
+    }
+
+    #[test]
+    fn boundaries_split_across_reads() {
+        let file = b"\r\n--Xy\r\r\n-- almost a boundary\r\n";
+        let multipart = parse_all(&body(file), &Limits::default()).unwrap();
+
+        assert_eq!(multipart.field("title"), Some("Hello, --Xy world"));
+        let upload = multipart.file("upload").unwrap();
+        assert_eq!(upload.filename, "a.bin");
+        assert_eq!(upload.content_type, "application/octet-stream");
+        assert_eq!(upload.size, file.len() as u64);
+        assert_eq!(upload.bytes().unwrap(), file);
+        assert_eq!(upload.path(), None);
+
+        // Buffered bodies parse the same.
+        let buffered = parse(&mut &body(file)[..], "XyZ", &Limits::default()).unwrap();
+        assert_eq!(buffered.file("upload").unwrap().bytes().unwrap(), file);
+    }
+
+    #[test]
+    fn missing_closing_boundary() {
+        let mut truncated = body(b"data");
+        truncated.truncate(truncated.len() - b"\r\n--XyZ--\r\nepilogue".len());
+        let err = parse_all(&truncated, &Limits::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = parse_all(b"no boundary at all", &Limits::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = parse_all(b"--XyZ\r\nContent-Disposition: form", &Limits::default());
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parts_over_the_limits() {
+        let dir = std::env::temp_dir().join(format!(
+            "just_serve_it-{}-multipart-limits",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let limits = Limits {
+            file_size: 100,
+            memory: 10,
+            temp_dir: Some(dir.clone()),
+            ..Limits::default()
+        };
+        assert!(parse_all(&body(&[7; 100]), &limits).is_ok());
+
+        let err = parse_all(&body(&[7; 101]), &limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+        // The partly written file went with the error.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let limits = Limits {
+            total_size: 200,
+            ..Limits::default()
+        };
+        let err = parse_all(&body(&[7; 100]), &limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn temp_files_removed_on_drop() {
+        let file: Vec<u8> = (0..50_000).map(|i| (i % 251) as u8).collect();
+        let limits = Limits {
+            memory: 1024,
+            ..Limits::default()
+        };
+        let multipart = parse(&mut &body(&file)[..], "XyZ", &limits).unwrap();
+
+        let upload = multipart.file("upload").unwrap();
+        let path = upload.path().unwrap().to_path_buf();
+        assert_eq!(std::fs::read(&path).unwrap(), file);
+        assert_eq!(upload.size, file.len() as u64);
+
+        // Clones share the file, it goes once the last one does.
+        let copy = upload.clone();
+        drop(multipart);
+        assert!(path.exists());
+        drop(copy);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::header::QualityItem;
+use crate::request::Request;
+use crate::response::compress::Compress;
+use crate::response::Response;
+use crate::routes::Router;
+
+use super::Middleware;
+use std::io::Result;
+use std::sync::Arc;
+
+// Codings offered to clients, preferred in this order on equal weight.
+const ENCODINGS: [&str; 2] = ["gzip", "deflate"];
+
+// Compresses responses with gzip or deflate, whichever the client's
+// `Accept-Encoding` weighs highest. Covers bodies sent with `send` and
+// friends as well as `stream`ed ones.
+pub struct Compression {
+    level: u32,
+    min_size: usize,
+    types: Option<Arc<Vec<QualityItem>>>,
+}
+
+impl Compression {
+    pub fn new() -> Self {
+        Self {
+            level: 6,
+            min_size: 1024,
+            types: None,
+        }
+    }
+
+    // From 0 (store only) to 9 (smallest output), 6 by default.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    // Bodies smaller than this, 1024 bytes by default, are sent as they
+    // are. Streamed bodies are always compressed.
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    // Replaces the built-in list of compressible types with these media
+    // ranges, e.g. `&["text/*", "application/json"]`.
+    pub fn content_types(mut self, types: &[&str]) -> Self {
+        self.types = Some(Arc::new(QualityItem::parse_list(&types.join(","))));
+        self
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, _routes: &mut Router, _req: &mut Request) -> Result<()> {
+        Ok(())
+    }
+
+    fn respond(&self, req: &Request, res: &mut Response) -> Result<()> {
+        res.compress = Some(Compress {
+            encoding: QualityItem::negotiate_encoding(&req.accept_encoding(), &ENCODINGS),
+            level: self.level,
+            min_size: self.min_size,
+            types: self.types.clone(),
+        });
+
+        Ok(())
+    }
+}
@@ -1,73 +1,412 @@
+use crate::embed::Asset;
+use crate::handler::Callback;
+use crate::header::typed::fmt_http_date;
 use crate::method;
 use crate::request::Request;
-use crate::response::Response;
-use crate::routes::{Route, Router};
+use crate::response::{RedirectKind, Response};
+use crate::routes::{Router, TrailingSlash};
 
-use super::Middleware;
-use std::io::Result;
-use std::{
-    fs,
-    path::{Path, PathBuf},
-};
+use super::{reject, Middleware};
+use std::collections::HashMap;
+use std::io::{Cursor, ErrorKind, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+mod cache;
+mod cache_control;
+mod listing;
+mod precompressed;
+mod resolve;
+#[cfg(test)]
+mod tests;
+
+pub use cache::FileCache;
+pub use resolve::{Dotfiles, Symlinks};
 
 pub struct ServeStatic {
-    root: String,
+    root: PathBuf,
+    prefix: String,
+    index: Vec<String>,
+    autoindex: bool,
+    symlinks: Symlinks,
+    dotfiles: Dotfiles,
+    spa: Option<String>,
+    api_prefixes: Vec<String>,
+    precompressed: bool,
+    cache_control: Vec<(String, String)>,
+    expires: bool,
+    cache: Option<FileCache>,
+    assets: Option<HashMap<&'static str, &'static Asset>>,
+}
+
+// Where the contents of a served file come from.
+#[derive(Clone)]
+enum Source {
+    Disk(PathBuf),
+    Embedded(&'static Asset),
+}
+
+impl Source {
+    // The name the content type is derived from.
+    fn name(&self) -> &str {
+        match self {
+            Source::Disk(path) => path.to_str().unwrap(),
+            Source::Embedded(asset) => asset.path,
+        }
+    }
+}
+
+enum Entry {
+    File(Source),
+    // Embedded directories have no path on disk.
+    Dir(Option<PathBuf>),
 }
 
 impl ServeStatic {
     pub fn dir(root: &str) -> Self {
         Self {
-            root: Self::sanitize_path(root).to_str().unwrap().to_string(),
+            root: PathBuf::from(root),
+            prefix: String::new(),
+            index: vec!["index.html".to_string(), "index.htm".to_string()],
+            autoindex: false,
+            symlinks: Symlinks::WithinRoot,
+            dotfiles: Dotfiles::Hide,
+            spa: None,
+            api_prefixes: Vec::new(),
+            precompressed: false,
+            cache_control: Vec::new(),
+            expires: false,
+            cache: None,
+            assets: None,
         }
     }
 
-    fn sanitize_path(path: &str) -> PathBuf {
-        let mut sanitized_path = PathBuf::new();
-        for component in path.split('/') {
-            if component == ".." || component == "." {
-                // Ignore '..' and '.' components
-                continue;
+    // Serves files bundled into the binary with `embed::generate` instead of
+    // a directory. Everything but `autoindex`, `symlinks` and `cache`
+    // applies to them as well.
+    pub fn embedded(assets: &'static [Asset]) -> Self {
+        let mut serve = Self::dir("");
+        serve.assets = Some(
+            assets
+                .iter()
+                .map(|asset| (asset.path.trim_start_matches('/'), asset))
+                .collect(),
+        );
+        serve
+    }
+
+    // Serves the root under a URL prefix, e.g. `/static/app.css` maps to
+    // `<root>/app.css` after `.at("/static")`.
+    pub fn at(mut self, prefix: &str) -> Self {
+        self.prefix = normalize_prefix(prefix);
+        self
+    }
+
+    // Single-page application mode: GETs that match neither a file nor a
+    // route are answered with `index` (relative to the root), except for
+    // paths that look like assets, i.e. whose last segment has an
+    // extension, and paths under an API prefix. Those still get a 404.
+    pub fn spa(mut self, index: &str) -> Self {
+        self.spa = Some(index.to_string());
+        self
+    }
+
+    // A URL prefix, such as `/api`, that is never answered by the SPA
+    // fallback.
+    pub fn api_prefix(mut self, prefix: &str) -> Self {
+        self.api_prefixes.push(normalize_prefix(prefix));
+        self
+    }
+
+    // The files tried, in order, when a directory is requested.
+    pub fn index(mut self, files: &[&str]) -> Self {
+        self.index = files.iter().map(|file| file.to_string()).collect();
+        self
+    }
+
+    // Lists directories without an index file, as HTML or as JSON for
+    // clients that prefer `application/json`.
+    pub fn autoindex(mut self, enabled: bool) -> Self {
+        self.autoindex = enabled;
+        self
+    }
+
+    // Serves `app.js.br` or `app.js.gz` in place of `app.js` to clients
+    // whose `Accept-Encoding` allows it.
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    // Sends `Cache-Control: <value>` for files matching `pattern`, e.g.
+    // `.cache_control("*.*.js", "public, max-age=31536000, immutable")`.
+    // `*` and `?` stay within a path segment and `**` spans several. Rules
+    // are tried in the order they were added.
+    pub fn cache_control(mut self, pattern: &str, value: &str) -> Self {
+        self.cache_control
+            .push((pattern.to_string(), value.to_string()));
+        self
+    }
+
+    // Also sends an `Expires` header derived from the matching
+    // `Cache-Control` rule, for caches that only understand HTTP/1.0.
+    pub fn expires(mut self, enabled: bool) -> Self {
+        self.expires = enabled;
+        self
+    }
+
+    // Keeps small files in memory, e.g.
+    // `.cache(FileCache::new(16 << 20, 256 << 10))`. Keep a clone of the
+    // cache to read its counters.
+    pub fn cache(mut self, cache: FileCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn symlinks(mut self, policy: Symlinks) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    pub fn dotfiles(mut self, policy: Dotfiles) -> Self {
+        self.dotfiles = policy;
+        self
+    }
+
+    // The canonical path of the file or directory a (still percent-encoded)
+    // request path refers to, guaranteed to be under the root unless
+    // symlinks are followed anywhere.
+    fn resolve(&self, request_path: &str) -> Result<PathBuf> {
+        resolve::resolve(&self.root, request_path, self.symlinks, self.dotfiles)
+    }
+
+    // Looks up what a request path below the prefix refers to, failing like
+    // `resolve`.
+    fn find(&self, relative: &str) -> Result<Entry> {
+        let assets = match &self.assets {
+            Some(assets) => assets,
+            None => {
+                let path = self.resolve(relative)?;
+
+                return if std::fs::metadata(&path)?.is_dir() {
+                    Ok(Entry::Dir(Some(path)))
+                } else {
+                    Ok(Entry::File(Source::Disk(path)))
+                };
             }
-            sanitized_path.push(component);
+        };
+
+        let relative = resolve::normalize(relative, self.dotfiles)?;
+        if let Some(asset) = assets.get(relative.as_str()) {
+            return Ok(Entry::File(Source::Embedded(asset)));
+        }
+
+        let dir = format!("{relative}/");
+        if relative.is_empty() || assets.keys().any(|path| path.starts_with(&dir)) {
+            return Ok(Entry::Dir(None));
         }
-        sanitized_path
+
+        Err(ErrorKind::NotFound.into())
+    }
+
+    fn find_file(&self, relative: &str) -> Option<Source> {
+        match self.find(relative) {
+            Ok(Entry::File(source)) => Some(source),
+            _ => None,
+        }
+    }
+
+    // Sends the file, or a precompressed copy of it when enabled and the
+    // client accepts one.
+    fn serve_file(&self, source: Source, relative: &str) -> Callback {
+        let precompressed = self.precompressed;
+        let variants = if precompressed {
+            precompressed::variants(relative, |path| self.find_file(path))
+        } else {
+            Vec::new()
+        };
+        let cache_control = cache_control::find(&self.cache_control, relative).map(str::to_string);
+        let expires = self.expires;
+        let cache = self.cache.clone();
+
+        // Sends `source`, a copy of `original` compressed with `encoding` if
+        // one is given, from the cache when there is one.
+        let send = move |response: &mut Response, source: &Source, original: &str, encoding| {
+            let path = match source {
+                Source::Disk(path) => path,
+                Source::Embedded(asset) => {
+                    return response.send_contents(
+                        Cursor::new(asset.contents),
+                        asset.contents.len() as u64,
+                        Some(UNIX_EPOCH + Duration::from_secs(asset.modified)),
+                        original,
+                        encoding,
+                    );
+                }
+            };
+
+            let cached = match &cache {
+                Some(cache) => cache.get(path)?,
+                None => None,
+            };
+
+            match cached {
+                Some(cached) => response.send_contents(
+                    Cursor::new(cached.contents.as_slice()),
+                    cached.contents.len() as u64,
+                    cached.modified,
+                    original,
+                    encoding,
+                ),
+                None => response.send_file_as(path.to_str().unwrap(), original, encoding),
+            }
+        };
+
+        Arc::new(move |req: Request, mut response: Response| {
+            let original = source.name();
+
+            if let Some(value) = &cache_control {
+                response.headers.set("Cache-Control", value);
+
+                if let Some(time) =
+                    cache_control::expires(value, SystemTime::now()).filter(|_| expires)
+                {
+                    response.headers.set("Expires", &fmt_http_date(time));
+                }
+            }
+
+            if !precompressed {
+                return send(&mut response, &source, original, None);
+            }
+
+            response.headers.append("Vary", "Accept-Encoding");
+
+            match precompressed::negotiate(&req.accept_encoding(), &variants) {
+                Some((encoding, variant)) => send(&mut response, variant, original, Some(encoding)),
+                None => send(&mut response, &source, original, None),
+            }
+        })
+    }
+
+    // The SPA entry point, if `path` is a client-side route.
+    fn fallback(&self, path: &str) -> Option<Callback> {
+        let index = self.spa.as_ref()?;
+        let last_segment = path.rsplit('/').next().unwrap_or_default();
+
+        if last_segment.contains('.')
+            || self
+                .api_prefixes
+                .iter()
+                .any(|prefix| strip_prefix(path, prefix).is_some())
+        {
+            return None;
+        }
+
+        let source = self.find_file(index)?;
+        Some(self.serve_file(source, index))
+    }
+
+    // Picks how a directory is answered: a redirect to add the trailing
+    // slash, its index file, or a listing.
+    fn serve_dir(&self, dir: Option<PathBuf>, relative: &str, req: &Request) -> Option<Callback> {
+        let path = req.path.clone().unwrap();
+
+        if !path.ends_with('/') {
+            let location = match &req.query {
+                Some(query) => format!("{path}/?{query}"),
+                None => format!("{path}/"),
+            };
+
+            return Some(Arc::new(move |_, mut res: Response| {
+                res.redirect(&location, RedirectKind::MovedPermanently)
+            }));
+        }
+
+        for index in &self.index {
+            let index = format!("{relative}{index}");
+
+            if let Some(source) = self.find_file(&index) {
+                return Some(self.serve_file(source, &index));
+            }
+        }
+
+        if let Some(dir) = dir.filter(|_| self.autoindex) {
+            let show_hidden = self.dotfiles == Dotfiles::Allow;
+            let is_root = relative == "/";
+            return Some(Arc::new(move |req, mut res| {
+                listing::send(&dir, is_root, show_hidden, &req, &mut res)
+            }));
+        }
+
+        None
     }
 }
 
 impl Middleware for ServeStatic {
     fn handle(&self, routes: &mut Router, req: &mut Request) -> Result<()> {
-        if let Some(method::Method::Get) = req.method {
-            if routes
-                .get(
-                    &req.method.as_ref().unwrap(),
-                    req.path.as_ref().unwrap().as_str(),
-                )
-                .is_none()
-            {
-                let sanitized_path = Self::sanitize_path(req.path.as_ref().unwrap().as_str());
-                let file_path = Path::new(&self.root).join(&sanitized_path);
-
-                if let Err(_) = fs::metadata(&file_path) {
-                    return Ok(());
-                }
+        // An earlier mount already claimed the request.
+        if req.handler.is_some() {
+            return Ok(());
+        }
+
+        let method = match &req.method {
+            Some(method @ method::Method::Get) => method,
+            _ => return Ok(()),
+        };
+        let path = req.path.clone().unwrap();
 
-                req.path = file_path.to_str().unwrap().to_string().into();
+        // Routes win over files, including the ones reached through the
+        // router's trailing slash policy.
+        let routed = routes.find(method, &path).is_some()
+            || (routes.trailing_slash != TrailingSlash::Strict
+                && routes.alternate(method, &path).is_some());
+        if routed {
+            return Ok(());
+        }
 
-                let route = Route::new(
-                    file_path.to_str().unwrap(),
-                    req.method.clone().unwrap(),
-                    server_static_file,
-                );
+        let relative = match strip_prefix(&path, &self.prefix) {
+            Some(relative) => relative,
+            None => return Ok(()),
+        };
 
-                routes.add(route);
+        let entry = match self.find(relative) {
+            Ok(entry) => entry,
+            Err(err) => {
+                req.handler = match err.kind() {
+                    ErrorKind::InvalidInput => Some(reject(400)),
+                    ErrorKind::PermissionDenied => Some(reject(403)),
+                    _ => self.fallback(&path),
+                };
+                return Ok(());
             }
-        }
+        };
+
+        req.handler = match entry {
+            Entry::Dir(dir) => self
+                .serve_dir(dir, relative, req)
+                .or_else(|| self.fallback(&path)),
+            Entry::File(source) => Some(self.serve_file(source, relative)),
+        };
 
         Ok(())
     }
 }
 
-fn server_static_file(request: Request, mut response: Response) -> Result<()> {
-    response.send_file(&request.path.unwrap())?;
-    Ok(())
+fn normalize_prefix(prefix: &str) -> String {
+    match prefix.trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("/{prefix}"),
+    }
+}
+
+// The part of `path` below `prefix`, if it is under it at all.
+fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(prefix)?;
+
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest)
+    } else {
+        None
+    }
 }
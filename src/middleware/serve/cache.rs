@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// A bounded least-recently-used cache of small static files, shared by
+// cloning. Entries are keyed by path and dropped once the file's modified
+// time or length changes.
+#[derive(Clone)]
+pub struct FileCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    max_size: u64,
+    max_file_size: u64,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Entries {
+    files: HashMap<PathBuf, Entry>,
+    // Paths by `last_used`, oldest first, so eviction doesn't need a scan.
+    recency: BTreeMap<u64, PathBuf>,
+    size: u64,
+    clock: u64,
+}
+
+struct Entry {
+    contents: Arc<Vec<u8>>,
+    modified: Option<SystemTime>,
+    last_used: u64,
+}
+
+pub(super) struct Cached {
+    pub(super) contents: Arc<Vec<u8>>,
+    pub(super) modified: Option<SystemTime>,
+}
+
+impl FileCache {
+    // Holds up to `max_size` bytes of file contents in total, from files of
+    // at most `max_file_size` bytes each. Larger files are read from disk.
+    pub fn new(max_size: u64, max_file_size: u64) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                max_size,
+                max_file_size,
+                entries: Mutex::new(Entries::default()),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.inner.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.inner.misses.load(Ordering::Relaxed)
+    }
+
+    // The number of cached files and their total size in bytes.
+    pub fn usage(&self) -> (usize, u64) {
+        let entries = self.inner.entries.lock().unwrap();
+        (entries.files.len(), entries.size)
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.inner.entries.lock().unwrap();
+        entries.files.clear();
+        entries.recency.clear();
+        entries.size = 0;
+    }
+
+    // The contents of `path`, from memory if they are still current. `None`
+    // for files too large to cache.
+    pub(super) fn get(&self, path: &Path) -> Result<Option<Cached>> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+
+        {
+            let mut entries = self.inner.entries.lock().unwrap();
+
+            if let Some(entry) = entries.files.get(path) {
+                if entry.modified == modified && entry.contents.len() as u64 == len {
+                    let contents = Arc::clone(&entry.contents);
+                    entries.touch(path);
+                    self.inner.hits.fetch_add(1, Ordering::Relaxed);
+
+                    return Ok(Some(Cached { contents, modified }));
+                }
+            }
+
+            entries.remove(path);
+        }
+
+        self.inner.misses.fetch_add(1, Ordering::Relaxed);
+
+        if len > self.inner.max_file_size || len > self.inner.max_size {
+            return Ok(None);
+        }
+
+        // Read outside the lock so a slow disk doesn't stall other hits.
+        let contents = Arc::new(fs::read(path)?);
+        let size = contents.len() as u64;
+
+        let mut entries = self.inner.entries.lock().unwrap();
+        // Another request may have cached the file while it was read.
+        entries.remove(path);
+
+        while entries.size + size > self.inner.max_size {
+            match entries.recency.pop_first() {
+                Some((_, oldest)) => {
+                    if let Some(evicted) = entries.files.remove(&oldest) {
+                        entries.size -= evicted.contents.len() as u64;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        let last_used = entries.tick();
+        entries.size += size;
+        entries.recency.insert(last_used, path.to_path_buf());
+        entries.files.insert(
+            path.to_path_buf(),
+            Entry {
+                contents: Arc::clone(&contents),
+                modified,
+                last_used,
+            },
+        );
+
+        Ok(Some(Cached { contents, modified }))
+    }
+}
+
+impl Entries {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn touch(&mut self, path: &Path) {
+        let clock = self.tick();
+
+        if let Some(entry) = self.files.get_mut(path) {
+            if let Some(path) = self.recency.remove(&entry.last_used) {
+                self.recency.insert(clock, path);
+            }
+            entry.last_used = clock;
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.files.remove(path) {
+            self.recency.remove(&entry.last_used);
+            self.size -= entry.contents.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("just_serve_it-{}-cache-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        for (file, size) in [("a", 10), ("b", 10), ("c", 10), ("big", 50)] {
+            fs::write(dir.join(file), vec![b'x'; size]).unwrap();
+        }
+
+        dir
+    }
+
+    fn cached(cache: &FileCache) -> Vec<String> {
+        let entries = cache.inner.entries.lock().unwrap();
+        entries
+            .recency
+            .values()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let dir = scratch("lru");
+        let cache = FileCache::new(25, 20);
+
+        cache.get(&dir.join("a")).unwrap().unwrap();
+        cache.get(&dir.join("b")).unwrap().unwrap();
+        // Using `a` again makes `b` the oldest.
+        cache.get(&dir.join("a")).unwrap().unwrap();
+        cache.get(&dir.join("c")).unwrap().unwrap();
+
+        assert_eq!(cached(&cache), ["a", "c"]);
+        assert_eq!(cache.usage(), (2, 20));
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+
+        // Too large to cache at all.
+        assert!(cache.get(&dir.join("big")).unwrap().is_none());
+        assert_eq!(cached(&cache), ["a", "c"]);
+
+        cache.clear();
+        assert_eq!(cache.usage(), (0, 0));
+        assert!(cached(&cache).is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drops_changed_files() {
+        let dir = scratch("stale");
+        let cache = FileCache::new(100, 100);
+
+        cache.get(&dir.join("a")).unwrap().unwrap();
+        fs::write(dir.join("a"), "changed").unwrap();
+
+        let fresh = cache.get(&dir.join("a")).unwrap().unwrap();
+        assert_eq!(fresh.contents.as_slice(), b"changed");
+        assert_eq!(cache.usage(), (1, 7));
+        assert_eq!(cache.misses(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
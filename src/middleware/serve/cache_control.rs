@@ -0,0 +1,165 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 9999-12-31 23:59:59, the last moment an HTTP date can express.
+const LATEST_HTTP_DATE: u64 = 253_402_300_799;
+
+// The value of the first rule whose pattern matches the file. Patterns
+// without a `/` are matched against the file name, others against the path
+// relative to the root.
+pub(super) fn find<'a>(rules: &'a [(String, String)], relative: &str) -> Option<&'a str> {
+    let relative = relative.trim_start_matches('/');
+    let name = relative.rsplit('/').next().unwrap_or_default();
+
+    rules
+        .iter()
+        .find(|(pattern, _)| {
+            let subject = if pattern.contains('/') {
+                relative
+            } else {
+                name
+            };
+            glob(
+                pattern.trim_start_matches('/').as_bytes(),
+                subject.as_bytes(),
+            )
+        })
+        .map(|(_, value)| value.as_str())
+}
+
+// The `Expires` equivalent of a `Cache-Control` value, for HTTP/1.0 caches.
+pub(super) fn expires(cache_control: &str, now: SystemTime) -> Option<SystemTime> {
+    let mut max_age = None;
+
+    for directive in cache_control.split(',').map(str::trim) {
+        let directive = directive.to_ascii_lowercase();
+
+        if directive == "no-cache" || directive == "no-store" {
+            return Some(UNIX_EPOCH);
+        }
+
+        if let Some(seconds) = directive.strip_prefix("max-age=") {
+            max_age = seconds.parse().ok();
+        }
+    }
+
+    // Huge ages are clamped instead of overflowing or outliving the format.
+    let latest = UNIX_EPOCH + Duration::from_secs(LATEST_HTTP_DATE);
+    max_age.map(|seconds| {
+        now.checked_add(Duration::from_secs(seconds))
+            .map_or(latest, |expires| expires.min(latest))
+    })
+}
+
+// `*` matches within a path segment, `**` across segments and `?` any one
+// character other than `/`.
+fn glob(pattern: &[u8], subject: &[u8]) -> bool {
+    match pattern {
+        [] => subject.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` only resumes at the start of a segment.
+            let (rest, whole_segments) = match rest.strip_prefix(b"/") {
+                Some(rest) => (rest, true),
+                None => (rest, false),
+            };
+
+            (0..=subject.len())
+                .filter(|&skip| !whole_segments || skip == 0 || subject[skip - 1] == b'/')
+                .any(|skip| glob(rest, &subject[skip..]))
+        }
+        [b'*', rest @ ..] => (0..=subject.len())
+            .take_while(|&skip| skip == 0 || subject[skip - 1] != b'/')
+            .any(|skip| glob(rest, &subject[skip..])),
+        [b'?', rest @ ..] => match subject {
+            [first, tail @ ..] if *first != b'/' => glob(rest, tail),
+            _ => false,
+        },
+        [expected, rest @ ..] => match subject {
+            [first, tail @ ..] if first == expected => glob(rest, tail),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, subject: &str) -> bool {
+        glob(pattern.as_bytes(), subject.as_bytes())
+    }
+
+    #[test]
+    fn star_stays_within_a_segment() {
+        assert!(matches("*.css", "app.css"));
+        assert!(matches("*.css", ".css"));
+        assert!(matches("assets/*.js", "assets/app.js"));
+        assert!(!matches("assets/*.js", "assets/vendor/app.js"));
+        assert!(!matches("*.css", "css/app.css"));
+        assert!(!matches("*.css", "app.css.map"));
+    }
+
+    #[test]
+    fn double_star_crosses_segments() {
+        assert!(matches("assets/**", "assets/app.js"));
+        assert!(matches("assets/**", "assets/vendor/lib/app.js"));
+        assert!(matches("**/*.js", "app.js"));
+        assert!(matches("**/*.js", "assets/vendor/app.js"));
+        assert!(matches("assets/**/app.js", "assets/app.js"));
+        assert!(matches("assets/**/app.js", "assets/a/b/app.js"));
+        // `**/` resumes at a segment boundary only.
+        assert!(!matches("assets/**/app.js", "assets/a/myapp.js"));
+        assert!(matches("assets/**app.js", "assets/a/myapp.js"));
+        assert!(!matches("assets/**", "static/app.js"));
+    }
+
+    #[test]
+    fn question_mark_is_one_character_but_not_a_slash() {
+        assert!(matches("app.?s", "app.js"));
+        assert!(!matches("app.?s", "app.s"));
+        assert!(!matches("a?b", "a/b"));
+        assert!(!matches("app.?", "app.js"));
+    }
+
+    #[test]
+    fn rules_match_names_or_relative_paths() {
+        let rules = [
+            ("/index.html".to_string(), "no-cache".to_string()),
+            ("assets/**".to_string(), "max-age=31536000".to_string()),
+            ("*.html".to_string(), "max-age=60".to_string()),
+        ];
+
+        assert_eq!(find(&rules, "/index.html"), Some("no-cache"));
+        assert_eq!(find(&rules, "/docs/index.html"), Some("max-age=60"));
+        assert_eq!(find(&rules, "/assets/x/app.js"), Some("max-age=31536000"));
+        assert_eq!(find(&rules, "/app.js"), None);
+    }
+
+    #[test]
+    fn expires_follows_max_age() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(
+            expires("public, max-age=60", now),
+            Some(now + Duration::from_secs(60))
+        );
+        assert_eq!(expires("MAX-AGE=60, no-cache", now), Some(UNIX_EPOCH));
+        assert_eq!(expires("no-store", now), Some(UNIX_EPOCH));
+        assert_eq!(expires("public", now), None);
+        assert_eq!(expires("max-age=soon", now), None);
+    }
+
+    #[test]
+    fn huge_ages_are_clamped_to_a_valid_date() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let latest = UNIX_EPOCH + Duration::from_secs(LATEST_HTTP_DATE);
+
+        for age in [u64::MAX, LATEST_HTTP_DATE] {
+            let expires = expires(&format!("max-age={age}"), now).unwrap();
+            assert_eq!(expires, latest);
+            assert_eq!(
+                httpdate::fmt_http_date(expires),
+                "Fri, 31 Dec 9999 23:59:59 GMT"
+            );
+        }
+    }
+}
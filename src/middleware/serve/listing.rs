@@ -0,0 +1,211 @@
+use crate::header::typed::fmt_http_date;
+use crate::request::Request;
+use crate::response::Response;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::json;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Result;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+struct Entry {
+    name: String,
+    dir: bool,
+    size: u64,
+    // Seconds since the Unix epoch.
+    modified: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Sort {
+    Name,
+    Size,
+    Modified,
+}
+
+// Sends the contents of `dir`, sorted by the `sort` (`name`, `size` or
+// `modified`) and `order` (`asc` or `desc`) query parameters. Directories
+// always come first. Dotfiles are left out unless `show_hidden` is set, and
+// the parent link is left out at the root of the mount.
+pub(super) fn send(
+    dir: &Path,
+    is_root: bool,
+    show_hidden: bool,
+    req: &Request,
+    res: &mut Response,
+) -> Result<()> {
+    let query: HashMap<String, String> = req
+        .query
+        .as_deref()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+
+    let sort = match query.get("sort").map(String::as_str) {
+        Some("size") => Sort::Size,
+        Some("modified") => Sort::Modified,
+        _ => Sort::Name,
+    };
+    let descending = query.get("order").map(String::as_str) == Some("desc");
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with('.') && !show_hidden {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+
+        entries.push(Entry {
+            name,
+            dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs()),
+        });
+    }
+
+    entries.sort_by(|a, b| {
+        let ordering = match sort {
+            Sort::Name => a.name.cmp(&b.name),
+            Sort::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)),
+            Sort::Modified => a
+                .modified
+                .cmp(&b.modified)
+                .then_with(|| a.name.cmp(&b.name)),
+        };
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+
+        match (a.dir, b.dir) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => ordering,
+        }
+    });
+
+    let path = req.path.clone().unwrap_or_default();
+
+    if prefers_json(req) {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "name": entry.name,
+                    "dir": entry.dir,
+                    "size": entry.size,
+                    "modified": entry.modified,
+                })
+            })
+            .collect();
+
+        return res.json(&json!({ "path": path, "entries": entries }));
+    }
+
+    res.html(&html(&path, is_root, &entries, sort, descending))
+}
+
+fn prefers_json(req: &Request) -> bool {
+    req.accept()
+        .iter()
+        .filter(|item| item.q > 0.0)
+        .find_map(|item| {
+            if item.value == "application/json" {
+                Some(true)
+            } else if item.matches("text/html") {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(false)
+}
+
+fn html(path: &str, is_root: bool, entries: &[Entry], sort: Sort, descending: bool) -> String {
+    let title = escape(path);
+
+    // Column headings toggle the order of the column already sorted by.
+    let heading = |label: &str, column: Sort, key: &str| {
+        let order = if column == sort && !descending {
+            "desc"
+        } else {
+            "asc"
+        };
+        format!("<th><a href=\"?sort={key}&amp;order={order}\">{label}</a></th>")
+    };
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n\
+         <body>\n<h1>Index of {title}</h1>\n<table>\n<tr>{}{}{}</tr>\n",
+        heading("Name", Sort::Name, "name"),
+        heading("Size", Sort::Size, "size"),
+        heading("Last modified", Sort::Modified, "modified"),
+    );
+
+    if !is_root {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+
+    for entry in entries {
+        let suffix = if entry.dir { "/" } else { "" };
+        let size = if entry.dir {
+            "-".to_string()
+        } else {
+            human_size(entry.size)
+        };
+        let modified = entry
+            .modified
+            .map(|secs| fmt_http_date(UNIX_EPOCH + Duration::from_secs(secs)))
+            .unwrap_or_default();
+
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{suffix}\">{}{suffix}</a></td><td>{size}</td><td>{modified}</td></tr>\n",
+            utf8_percent_encode(&entry.name, SEGMENT),
+            escape(&entry.name),
+        ));
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
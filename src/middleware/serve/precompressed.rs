@@ -0,0 +1,30 @@
+use super::Source;
+use crate::header::QualityItem;
+
+// Sibling suffixes and the `Content-Encoding` they are sent with, in order
+// of preference when the client accepts several equally.
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gz", "gzip")];
+
+pub(super) type Variant = (&'static str, Source);
+
+// The compressed copies of `relative` that exist, as found by `find`.
+pub(super) fn variants(relative: &str, find: impl Fn(&str) -> Option<Source>) -> Vec<Variant> {
+    ENCODINGS
+        .iter()
+        .filter_map(|(suffix, encoding)| {
+            let source = find(&format!("{relative}.{suffix}"))?;
+            Some((*encoding, source))
+        })
+        .collect()
+}
+
+// Picks the variant the client weighs highest, if it accepts any.
+pub(super) fn negotiate<'a>(
+    accepted: &[QualityItem],
+    variants: &'a [Variant],
+) -> Option<&'a Variant> {
+    let offers: Vec<&str> = variants.iter().map(|(encoding, _)| *encoding).collect();
+    let encoding = QualityItem::negotiate_encoding(accepted, &offers)?;
+
+    variants.iter().find(|(offered, _)| *offered == encoding)
+}
@@ -0,0 +1,110 @@
+use percent_encoding::percent_decode_str;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+// Whether symbolic links under the root are followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symlinks {
+    // Follow links whose target is still inside the root.
+    #[default]
+    WithinRoot,
+    // Follow links wherever they point.
+    Follow,
+    // Never serve a path that goes through a link.
+    Deny,
+}
+
+// How files and directories whose name starts with a dot are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dotfiles {
+    // Act as if they don't exist.
+    #[default]
+    Hide,
+    // Answer with 403 Forbidden.
+    Deny,
+    // Serve them like any other file.
+    Allow,
+}
+
+// Maps a request path onto a file under `root`. Fails with `InvalidInput`
+// for malformed or traversing paths, `PermissionDenied` for denied
+// dotfiles and `NotFound` for anything that doesn't exist, is hidden or
+// resolves outside the root.
+pub(super) fn resolve(
+    root: &Path,
+    request_path: &str,
+    symlinks: Symlinks,
+    dotfiles: Dotfiles,
+) -> Result<PathBuf> {
+    let relative = PathBuf::from(normalize(request_path, dotfiles)?);
+    let root = fs::canonicalize(root)?;
+    let joined = root.join(&relative);
+
+    if symlinks == Symlinks::Deny {
+        let mut current = root.clone();
+        for component in relative.components() {
+            current.push(component);
+            if fs::symlink_metadata(&current)?.file_type().is_symlink() {
+                return Err(ErrorKind::NotFound.into());
+            }
+        }
+    }
+
+    let resolved = fs::canonicalize(joined)?;
+
+    // A link can lead to a dotfile the request path never named, so the
+    // policy applies to where it ends up as well. Outside the root only the
+    // file itself is checked.
+    let names: Vec<_> = match resolved.strip_prefix(&root) {
+        Ok(inside) => inside.iter().collect(),
+        Err(_) if symlinks == Symlinks::Follow => resolved.file_name().into_iter().collect(),
+        Err(_) => return Err(ErrorKind::NotFound.into()),
+    };
+    for name in names {
+        check_dotfile(&name.to_string_lossy(), dotfiles)?;
+    }
+
+    Ok(resolved)
+}
+
+// Decodes and validates a request path, returning its segments joined by
+// `/` without a leading slash. Fails like `resolve`.
+pub(super) fn normalize(request_path: &str, dotfiles: Dotfiles) -> Result<String> {
+    let decoded = percent_decode_str(request_path)
+        .decode_utf8()
+        .map_err(|_| invalid("path is not valid UTF-8"))?;
+
+    if decoded.contains('\0') || decoded.contains('\\') {
+        return Err(invalid("path contains a NUL byte or backslash"));
+    }
+
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(invalid("path traverses above its root")),
+            _ => check_dotfile(segment, dotfiles)?,
+        }
+
+        segments.push(segment);
+    }
+
+    Ok(segments.join("/"))
+}
+
+fn check_dotfile(name: &str, dotfiles: Dotfiles) -> Result<()> {
+    if !name.starts_with('.') {
+        return Ok(());
+    }
+
+    match dotfiles {
+        Dotfiles::Hide => Err(ErrorKind::NotFound.into()),
+        Dotfiles::Deny => Err(ErrorKind::PermissionDenied.into()),
+        Dotfiles::Allow => Ok(()),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,359 @@
+use super::{Dotfiles, ServeStatic, Symlinks};
+use crate::method::Method;
+use crate::response::Response;
+use crate::routes::{Route, Router, TrailingSlash};
+use crate::testing::{fixture, get, Sent};
+use std::fs;
+use std::io::ErrorKind;
+
+fn serve(dir: &std::path::Path) -> ServeStatic {
+    ServeStatic::dir(dir.join("public").to_str().unwrap())
+}
+
+#[test]
+fn resolves_files_under_the_root() {
+    let dir = fixture("files");
+    let serve = serve(&dir);
+
+    let resolved = serve.resolve("/a.txt").unwrap();
+    assert!(resolved.ends_with("public/a.txt"));
+
+    let resolved = serve.resolve("/docs/./guide.txt").unwrap();
+    assert!(resolved.ends_with("public/docs/guide.txt"));
+
+    let resolved = serve.resolve("/%64ocs/guide.txt").unwrap();
+    assert!(resolved.ends_with("public/docs/guide.txt"));
+
+    assert_eq!(
+        serve.resolve("/missing.txt").unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rejects_traversal_attempts() {
+    let dir = fixture("traversal");
+    let serve = serve(&dir);
+
+    for path in [
+        "/../secret.txt",
+        "/docs/../../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/%2e%2e%2fsecret.txt",
+        "/%2E%2E%2Fsecret.txt",
+        "/docs/..%2f..%2fsecret.txt",
+        "/..%5csecret.txt",
+        "/docs\\..\\..\\secret.txt",
+        "/a.txt%00.png",
+        "/%ff.txt",
+    ] {
+        let err = serve.resolve(path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput, "{path}");
+    }
+
+    // Decoding happens once, so a double encoded `../` is a literal name.
+    let err = serve.resolve("/%252e%252e%252fsecret.txt").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn hides_dotfiles_by_default() {
+    let dir = fixture("dotfiles");
+
+    for path in ["/.env", "/.git/config", "/%2eenv", "/.git/"] {
+        let err = serve(&dir).resolve(path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound, "{path}");
+    }
+
+    let denied = serve(&dir).dotfiles(Dotfiles::Deny);
+    assert_eq!(
+        denied.resolve("/.env").unwrap_err().kind(),
+        ErrorKind::PermissionDenied
+    );
+
+    let allowed = serve(&dir).dotfiles(Dotfiles::Allow);
+    assert!(allowed.resolve("/.git/config").is_ok());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn applies_the_symlink_policy() {
+    let dir = fixture("symlinks");
+
+    let within = serve(&dir);
+    assert!(within
+        .resolve("/inside")
+        .unwrap()
+        .ends_with("docs/guide.txt"));
+    assert_eq!(
+        within.resolve("/outside").unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+
+    let follow = serve(&dir).symlinks(Symlinks::Follow);
+    assert!(follow.resolve("/outside").unwrap().ends_with("secret.txt"));
+
+    let deny = serve(&dir).symlinks(Symlinks::Deny);
+    assert_eq!(
+        deny.resolve("/inside").unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+    assert!(deny.resolve("/docs/guide.txt").is_ok());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn applies_the_dotfile_policy_to_link_targets() {
+    let dir = fixture("dotfile-links");
+
+    for path in ["/env", "/git/config"] {
+        let err = serve(&dir).resolve(path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound, "{path}");
+
+        let denied = serve(&dir).dotfiles(Dotfiles::Deny);
+        let err = denied.resolve(path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied, "{path}");
+
+        let allowed = serve(&dir).dotfiles(Dotfiles::Allow);
+        assert!(allowed.resolve(path).is_ok(), "{path}");
+    }
+
+    // Followed links outside the root are checked by their own name.
+    fs::write(dir.join(".token"), "token").unwrap();
+    std::os::unix::fs::symlink("../.token", dir.join("public/token")).unwrap();
+    let follow = serve(&dir).symlinks(Symlinks::Follow);
+    assert!(follow.resolve("/outside").is_ok());
+    assert_eq!(
+        follow.resolve("/token").unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+    let follow_all = serve(&dir)
+        .symlinks(Symlinks::Follow)
+        .dotfiles(Dotfiles::Allow);
+    assert!(follow_all.resolve("/token").unwrap().ends_with(".token"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn names(sent: &Sent) -> Vec<String> {
+    let listing: serde_json::Value = serde_json::from_slice(&sent.body).unwrap();
+    listing["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn redirects_directories_to_their_slash_form() {
+    let dir = fixture("dir-redirect");
+    let serve = serve(&dir);
+    let mut routes = Router::new();
+
+    let sent = get(&serve, &mut routes, "/docs", &[]).unwrap();
+    assert_eq!(sent.status, 301);
+    assert_eq!(sent.header("Location"), Some("/docs/"));
+
+    let sent = get(&serve, &mut routes, "/docs?page=2", &[]).unwrap();
+    assert_eq!(sent.header("Location"), Some("/docs/?page=2"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn serves_index_files() {
+    let dir = fixture("dir-index");
+    let mut routes = Router::new();
+
+    let sent = get(&serve(&dir), &mut routes, "/", &[]).unwrap();
+    assert_eq!(sent.status, 200);
+    assert_eq!(sent.body, b"<h1>home</h1>");
+
+    // Without an index or a listing the directory isn't claimed.
+    assert!(get(&serve(&dir), &mut routes, "/docs/", &[]).is_none());
+
+    let custom = serve(&dir).index(&["missing.html", "guide.txt"]);
+    let sent = get(&custom, &mut routes, "/docs/", &[]).unwrap();
+    assert_eq!(sent.body, b"guide");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lists_directories_sorted_as_asked() {
+    let dir = fixture("dir-listing");
+    fs::create_dir_all(dir.join("public/list/sub")).unwrap();
+    fs::write(dir.join("public/list/a.txt"), "a".repeat(10)).unwrap();
+    fs::write(dir.join("public/list/b.txt"), "b").unwrap();
+    fs::write(dir.join("public/list/.hidden"), "").unwrap();
+
+    let serve = serve(&dir).autoindex(true);
+    let mut routes = Router::new();
+    let json = ["Accept: application/json"];
+
+    let sent = get(&serve, &mut routes, "/list/", &json).unwrap();
+    assert_eq!(names(&sent), ["sub", "a.txt", "b.txt"]);
+
+    // Directories stay first whichever way files are sorted.
+    let sent = get(&serve, &mut routes, "/list/?sort=size", &json).unwrap();
+    assert_eq!(names(&sent), ["sub", "b.txt", "a.txt"]);
+    let sent = get(&serve, &mut routes, "/list/?sort=name&order=desc", &json).unwrap();
+    assert_eq!(names(&sent), ["sub", "b.txt", "a.txt"]);
+
+    let sent = get(&serve, &mut routes, "/list/?sort=size&order=desc", &[]).unwrap();
+    let html = String::from_utf8(sent.body).unwrap();
+    assert!(html.contains("<a href=\"../\">"));
+    assert!(html.contains("?sort=size&amp;order=asc"));
+    assert!(html.find("a.txt").unwrap() < html.find("b.txt").unwrap());
+    assert!(!html.contains(".hidden"));
+
+    let shown = get(
+        &serve.dotfiles(Dotfiles::Allow),
+        &mut routes,
+        "/list/",
+        &json,
+    );
+    assert!(names(&shown.unwrap()).contains(&".hidden".to_string()));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn strips_the_mount_prefix() {
+    let dir = fixture("prefix");
+    let serve = serve(&dir).at("/static/");
+    let mut routes = Router::new();
+
+    let sent = get(&serve, &mut routes, "/static/a.txt", &[]).unwrap();
+    assert_eq!(sent.body, b"a");
+    let sent = get(&serve, &mut routes, "/static/", &[]).unwrap();
+    assert_eq!(sent.body, b"<h1>home</h1>");
+    let sent = get(&serve, &mut routes, "/static", &[]).unwrap();
+    assert_eq!(sent.header("Location"), Some("/static/"));
+
+    for path in ["/a.txt", "/staticx/a.txt", "/other/static/a.txt"] {
+        assert!(get(&serve, &mut routes, path, &[]).is_none(), "{path}");
+    }
+
+    // The root of the mount has no parent link.
+    let listing = serve.index(&[]).autoindex(true);
+    let sent = get(&listing, &mut routes, "/static/", &[]).unwrap();
+    assert!(!String::from_utf8(sent.body).unwrap().contains("../"));
+    let sent = get(&listing, &mut routes, "/static/docs/", &[]).unwrap();
+    assert!(String::from_utf8(sent.body).unwrap().contains("../"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn routes_win_over_files() {
+    let dir = fixture("collision");
+    let serve = serve(&dir);
+    let mut routes = Router::new();
+    routes.add(Route::new("/a.txt", Method::Get, |mut res: Response| {
+        res.send("route")
+    }));
+    routes.add(Route::new("/docs", Method::Get, |mut res: Response| {
+        res.send("route")
+    }));
+
+    assert!(get(&serve, &mut routes, "/a.txt", &[]).is_none());
+    assert!(get(&serve, &mut routes, "/docs", &[]).is_none());
+
+    // Under a strict policy `/docs/` is a different path, the directory.
+    let listing = serve.autoindex(true);
+    assert!(get(&listing, &mut routes, "/docs/", &[]).is_some());
+
+    // Otherwise it reaches the route too.
+    routes.trailing_slash = TrailingSlash::Redirect;
+    assert!(get(&listing, &mut routes, "/docs/", &[]).is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn falls_back_to_the_spa_entry_point() {
+    let dir = fixture("spa");
+    let app = serve(&dir).at("/app").spa("/index.html");
+    let mut routes = Router::new();
+
+    for path in ["/app/users/7", "/app/missing", "/app/docs/missing/"] {
+        let sent = get(&app, &mut routes, path, &[]).unwrap();
+        assert_eq!(sent.body, b"<h1>home</h1>", "{path}");
+    }
+    let sent = get(&app, &mut routes, "/app/a.txt", &[]).unwrap();
+    assert_eq!(sent.body, b"a");
+
+    // Missing assets still get a 404.
+    for path in ["/app/missing.js", "/app/users/7.json"] {
+        assert!(get(&app, &mut routes, path, &[]).is_none(), "{path}");
+    }
+
+    // And so does anything under an API prefix, a whole segment at a time.
+    let serve = serve(&dir).spa("/index.html").api_prefix("/api/");
+    for path in ["/api", "/api/", "/api/users/7"] {
+        assert!(get(&serve, &mut routes, path, &[]).is_none(), "{path}");
+    }
+    let sent = get(&serve, &mut routes, "/apiary", &[]).unwrap();
+    assert_eq!(sent.body, b"<h1>home</h1>");
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn negotiates_precompressed_siblings() {
+    let dir = fixture("precompressed");
+    fs::write(dir.join("public/app.js"), "plain").unwrap();
+    fs::write(dir.join("public/app.js.br"), "brotli").unwrap();
+    fs::write(dir.join("public/app.js.gz"), "gzip").unwrap();
+    fs::write(dir.join("public/a.txt.gz"), "gzip").unwrap();
+
+    let enabled = serve(&dir).precompressed(true);
+    let mut routes = Router::new();
+
+    for (accept, body, encoding) in [
+        ("gzip, br", "brotli", Some("br")),
+        ("gzip", "gzip", Some("gzip")),
+        ("br;q=0.5, gzip", "gzip", Some("gzip")),
+        ("*", "brotli", Some("br")),
+        ("gzip;q=0, br;q=0", "plain", None),
+        ("identity", "plain", None),
+    ] {
+        let header = format!("Accept-Encoding: {accept}");
+        let sent = get(&enabled, &mut routes, "/app.js", &[&header]).unwrap();
+        assert_eq!(sent.body, body.as_bytes(), "{accept}");
+        assert_eq!(sent.header("Content-Encoding"), encoding, "{accept}");
+        assert_eq!(sent.header("Vary"), Some("Accept-Encoding"), "{accept}");
+        // The type is the original file's.
+        assert!(sent.header("Content-Type").unwrap().contains("javascript"));
+    }
+
+    // Without the client asking, and for files without a matching copy,
+    // the original is sent.
+    let sent = get(&enabled, &mut routes, "/app.js", &[]).unwrap();
+    assert_eq!(sent.body, b"plain");
+    let sent = get(&enabled, &mut routes, "/a.txt", &["Accept-Encoding: br"]).unwrap();
+    assert_eq!(sent.body, b"a");
+
+    // Copies are only looked for when enabled.
+    let plain = get(
+        &serve(&dir),
+        &mut routes,
+        "/app.js",
+        &["Accept-Encoding: gzip"],
+    );
+    let plain = plain.unwrap();
+    assert_eq!(plain.body, b"plain");
+    assert_eq!(plain.header("Vary"), None);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,240 @@
+use super::Middleware;
+use crate::cookie::{Cookie, SameSite};
+use crate::response::Response;
+use crate::session::{self, generate_id, Record, SessionStore, State};
+use crate::{request::Request, routes::Router};
+use std::io::Result;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone)]
+pub struct Session {
+    store: Arc<dyn SessionStore>,
+    cookie_name: String,
+    ttl: Duration,
+    secure: bool,
+}
+
+impl Session {
+    pub fn new(store: impl SessionStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie_name: "sid".to_string(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            secure: false,
+        }
+    }
+
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    // Spawns a background thread that drops expired sessions from the store.
+    pub fn cleanup_every(self, interval: Duration) -> Self {
+        let store = Arc::clone(&self.store);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(err) = store.cleanup() {
+                println!("ERROR: session cleanup failed: {:?}", err);
+            }
+        });
+
+        self
+    }
+
+    fn cookie(&self, id: &str) -> Cookie {
+        let cookie = Cookie::new(&self.cookie_name, id)
+            .path("/")
+            .http_only()
+            .same_site(SameSite::Lax)
+            .max_age(self.ttl);
+
+        if self.secure {
+            cookie.secure()
+        } else {
+            cookie
+        }
+    }
+
+    fn load(&self, req: &Request) -> Result<session::Session> {
+        if let Some(id) = req.cookie(&self.cookie_name) {
+            // An unreadable record is treated like a missing one.
+            if let Ok(Some(record)) = self.store.load(&id) {
+                return Ok(session::Session::new(id, record.data, false));
+            }
+        }
+
+        Ok(session::Session::new(
+            generate_id()?,
+            Default::default(),
+            true,
+        ))
+    }
+
+    fn commit(&self, session: &session::Session, res: &mut Response) -> Result<()> {
+        let mut inner = session.lock();
+
+        if let Some(previous_id) = inner.previous_id.take() {
+            self.store.destroy(&previous_id)?;
+        }
+
+        match inner.state {
+            State::Destroyed => {
+                if !inner.is_new {
+                    self.store.destroy(&inner.id)?;
+                    res.remove_cookie(&self.cookie_name)?;
+                }
+            }
+            // Brand new sessions that were never written to aren't worth
+            // storing; existing ones are re-saved to push their expiry out.
+            State::Unchanged if inner.is_new => {}
+            State::Unchanged | State::Changed => {
+                let record = Record {
+                    data: inner.data.clone(),
+                    expires: SystemTime::now() + self.ttl,
+                };
+                self.store.save(&inner.id, &record)?;
+                res.set_cookie(self.cookie(&inner.id))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Middleware for Session {
+    fn handle(&self, _routes: &mut Router, req: &mut Request) -> Result<()> {
+        req.session = Some(self.load(req)?);
+        Ok(())
+    }
+
+    fn respond(&self, req: &Request, res: &mut Response) -> Result<()> {
+        if let Some(session) = req.session.clone() {
+            let middleware = self.clone();
+            res.before_send(move |res| middleware.commit(&session, res));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::MemoryStore;
+    use crate::testing::{self, Sent};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    // One request through the middleware, with `handler` standing in for
+    // the route, and the id its cookie carried back.
+    fn exchange(
+        middleware: &Session,
+        id: Option<&str>,
+        handler: impl FnOnce(&session::Session),
+    ) -> (Sent, Option<String>) {
+        let cookie = id.map_or(String::new(), |id| format!("Cookie: sid={id}\r\n"));
+        let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\n{cookie}\r\n");
+        let mut req = Request::read(Cursor::new(raw.into_bytes()), 1024).unwrap();
+
+        middleware.handle(&mut Router::new(), &mut req).unwrap();
+        handler(req.session.as_ref().unwrap());
+
+        let (mut res, client) = testing::response();
+        middleware.respond(&req, &mut res).unwrap();
+        res.send("ok").unwrap();
+        drop(res);
+
+        let sent = testing::read(client);
+        let id = sent
+            .header("Set-Cookie")
+            .and_then(|cookie| cookie.strip_prefix("sid="))
+            .map(|rest| rest[..rest.find(';').unwrap()].to_string());
+        (sent, id)
+    }
+
+    fn stored(store: &Arc<dyn SessionStore>, id: &str) -> bool {
+        store.load(id).unwrap().is_some()
+    }
+
+    #[test]
+    fn untouched_new_sessions_are_not_stored() {
+        let middleware = Session::new(MemoryStore::new());
+        let (sent, id) = exchange(&middleware, None, |_| {});
+        assert_eq!(sent.header("Set-Cookie"), None);
+        assert_eq!(id, None);
+    }
+
+    #[test]
+    fn sessions_carry_over_until_they_expire() {
+        let middleware = Session::new(MemoryStore::new());
+        let (_, id) = exchange(&middleware, None, |session| {
+            session.insert("user", 7).unwrap();
+        });
+        let id = id.unwrap();
+
+        exchange(&middleware, Some(&id), |session| {
+            assert_eq!(session.id(), id);
+            assert_eq!(session.get::<u32>("user"), Some(7));
+        });
+
+        let expired = Record {
+            data: HashMap::new(),
+            expires: SystemTime::now() - Duration::from_secs(1),
+        };
+        middleware.store.save(&id, &expired).unwrap();
+        exchange(&middleware, Some(&id), |session| {
+            assert_ne!(session.id(), id);
+            assert!(!session.contains("user"));
+        });
+    }
+
+    #[test]
+    fn regenerate_destroys_the_old_id() {
+        let middleware = Session::new(MemoryStore::new());
+        let (_, old) = exchange(&middleware, None, |session| {
+            session.insert("user", 7).unwrap();
+        });
+        let old = old.unwrap();
+
+        let (_, new) = exchange(&middleware, Some(&old), |session| {
+            session.regenerate().unwrap();
+        });
+        let new = new.unwrap();
+
+        assert_ne!(new, old);
+        assert!(!stored(&middleware.store, &old));
+        assert!(stored(&middleware.store, &new));
+
+        // The old cookie no longer finds the data.
+        exchange(&middleware, Some(&old), |session| {
+            assert!(!session.contains("user"));
+        });
+    }
+
+    #[test]
+    fn destroy_removes_the_record_and_the_cookie() {
+        let middleware = Session::new(MemoryStore::new());
+        let (_, id) = exchange(&middleware, None, |session| {
+            session.insert("user", 7).unwrap();
+        });
+        let id = id.unwrap();
+
+        let (sent, cookie_id) = exchange(&middleware, Some(&id), |session| session.destroy());
+        assert_eq!(cookie_id.as_deref(), Some(""));
+        assert!(sent.header("Set-Cookie").unwrap().contains("Max-Age=0"));
+        assert!(!stored(&middleware.store, &id));
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const DEFAULT: &str = "application/octet-stream";
+
+const TYPES: &[(&str, &str)] = &[
+    // Text and documents
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("xhtml", "application/xhtml+xml"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("cjs", "text/javascript"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("jsonld", "application/ld+json"),
+    ("webmanifest", "application/manifest+json"),
+    ("xml", "application/xml"),
+    ("xsl", "application/xml"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("txt", "text/plain"),
+    ("text", "text/plain"),
+    ("log", "text/plain"),
+    ("conf", "text/plain"),
+    ("ini", "text/plain"),
+    ("csv", "text/csv"),
+    ("tsv", "text/tab-separated-values"),
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("ics", "text/calendar"),
+    ("vtt", "text/vtt"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("toml", "application/toml"),
+    ("rtf", "application/rtf"),
+    ("pdf", "application/pdf"),
+    ("epub", "application/epub+zip"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    // Images
+    ("png", "image/png"),
+    ("apng", "image/apng"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("jpe", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("heic", "image/heic"),
+    ("heif", "image/heif"),
+    ("jxl", "image/jxl"),
+    ("svg", "image/svg+xml"),
+    ("svgz", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("cur", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    // Fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("eot", "application/vnd.ms-fontobject"),
+    // Audio
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("ogg", "audio/ogg"),
+    ("oga", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("flac", "audio/flac"),
+    ("aac", "audio/aac"),
+    ("m4a", "audio/mp4"),
+    ("weba", "audio/webm"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    // Video
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("mkv", "video/x-matroska"),
+    ("mpeg", "video/mpeg"),
+    ("mpg", "video/mpeg"),
+    ("ts", "video/mp2t"),
+    ("3gp", "video/3gpp"),
+    // Archives and binaries
+    ("wasm", "application/wasm"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("bz2", "application/x-bzip2"),
+    ("xz", "application/x-xz"),
+    ("7z", "application/x-7z-compressed"),
+    ("rar", "application/vnd.rar"),
+    ("jar", "application/java-archive"),
+    ("sh", "application/x-sh"),
+    ("bin", DEFAULT),
+    ("exe", DEFAULT),
+    ("dll", DEFAULT),
+];
+
+// Leading bytes of common binary formats, checked in order.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\0asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"BM", "image/bmp"),
+    (b"OggS", "audio/ogg"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+];
+
+// Case-insensitive lookup in the built-in table.
+pub fn from_extension(extension: &str) -> Option<&'static str> {
+    TYPES
+        .iter()
+        .find(|(ext, _)| ext.eq_ignore_ascii_case(extension))
+        .map(|(_, mime)| *mime)
+}
+
+// Guesses a type from the first bytes of a file. Empty content gives no
+// evidence either way, so it is left to the default type.
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+        return Some(mime);
+    }
+
+    match bytes {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some("audio/wav"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("video/mp4"),
+        _ => sniff_text(bytes),
+    }
+}
+
+fn sniff_text(bytes: &[u8]) -> Option<&'static str> {
+    // `bytes` is usually just the head of a file, which may end partway
+    // through a multibyte character.
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) if err.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..err.valid_up_to()]).ok()?
+        }
+        Err(_) => return None,
+    };
+    let start = text.trim_start().to_ascii_lowercase();
+
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        Some("text/html")
+    } else if start.starts_with("<svg") {
+        Some("image/svg+xml")
+    } else if start.starts_with("<?xml") {
+        Some("application/xml")
+    } else if !text.contains('\0') {
+        Some("text/plain")
+    } else {
+        None
+    }
+}
+
+// Adds `charset=utf-8` to types whose content is text.
+pub fn with_charset(mime: &str) -> String {
+    let textual = mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime,
+            "application/json"
+                | "application/xml"
+                | "application/javascript"
+                | "application/yaml"
+                | "application/toml"
+        );
+
+    if textual && !mime.contains("charset") {
+        format!("{mime}; charset=utf-8")
+    } else {
+        mime.to_string()
+    }
+}
+
+// Whether content of this type usually shrinks when compressed. Images,
+// audio, video, fonts other than the legacy ones and archives are already
+// compressed.
+pub fn is_compressible(mime: &str) -> bool {
+    let mime = mime.to_ascii_lowercase();
+
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json"
+                | "application/xml"
+                | "application/javascript"
+                | "application/yaml"
+                | "application/toml"
+                | "application/wasm"
+                | "application/rtf"
+                | "application/x-sh"
+                | "application/vnd.ms-fontobject"
+                | "font/ttf"
+                | "font/otf"
+                | "image/x-icon"
+                | "image/bmp"
+        )
+}
+
+// The built-in table plus a server's own registrations, which take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct MimeTypes {
+    overrides: HashMap<String, String>,
+    sniff: bool,
+}
+
+impl MimeTypes {
+    pub fn new() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            sniff: false,
+        }
+    }
+
+    pub fn register(&mut self, extension: &str, mime: &str) {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.overrides.insert(extension, mime.to_string());
+    }
+
+    pub fn set_sniffing(&mut self, sniff: bool) {
+        self.sniff = sniff;
+    }
+
+    pub fn sniffing(&self) -> bool {
+        self.sniff
+    }
+
+    pub fn lookup(&self, extension: &str) -> Option<String> {
+        let extension = extension.to_ascii_lowercase();
+        self.overrides
+            .get(&extension)
+            .cloned()
+            .or_else(|| from_extension(&extension).map(str::to_string))
+    }
+
+    // The `Content-Type` for a file. `head` is consulted when sniffing is
+    // enabled and the extension is unknown.
+    pub fn for_path(&self, path: &str, head: Option<&[u8]>) -> String {
+        let by_extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.lookup(ext));
+
+        let mime = match by_extension {
+            Some(mime) => mime,
+            None => match head.filter(|_| self.sniff).and_then(sniff) {
+                Some(mime) => mime.to_string(),
+                None => DEFAULT.to_string(),
+            },
+        };
+
+        with_charset(&mime)
+    }
+}
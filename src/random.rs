@@ -0,0 +1,20 @@
+use std::io::{Error, Result};
+
+// `len` random bytes from the OS, as lowercase hex.
+pub(crate) fn hex(len: usize) -> Result<String> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).map_err(|err| Error::other(err.to_string()))?;
+
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn hex_is_unique() {
+        let hex = super::hex(16).unwrap();
+        assert_eq!(hex.len(), 32);
+        assert!(hex.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(hex, super::hex(16).unwrap());
+    }
+}
@@ -1,14 +1,42 @@
 use super::body::Body;
-use super::header::Header;
+use super::cookie::{CookieJar, Keys};
+use super::extract::StateMap;
+use super::handler::Callback;
+use super::header::{
+    Authorization, ContentType, Header, Host, IfMatch, IfNoneMatch, QualityItem, Range,
+};
 use super::method::Method;
+use super::session::Session;
+use super::version::Version;
+use decode::{BodyReader, Exact};
+use std::collections::HashMap;
+use std::io::{Cursor, Error, ErrorKind, Read, Result};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(Clone)]
+mod decode;
+
+// Heads larger than this are rejected rather than buffered.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Default)]
 pub struct Request {
     pub method: Option<Method>,
     pub path: Option<String>,
-    pub version: Option<String>,
+    pub query: Option<String>,
+    pub version: Option<Version>,
     pub headers: Header,
     pub body: Option<Body>,
+    pub params: Vec<(String, String)>,
+    pub(crate) state: Arc<StateMap>,
+    pub(crate) keys: Option<Arc<Keys>>,
+    pub(crate) session: Option<Session>,
+    // Set by middleware that answers the request itself, e.g. `ServeStatic`.
+    pub(crate) handler: Option<Callback>,
+    // The rest of a body that is parsed as it arrives rather than read up
+    // front, see `read`.
+    pub(crate) body_reader: Option<Arc<Mutex<BodyReader>>>,
 }
 
 impl Request {
@@ -16,9 +44,16 @@ impl Request {
         Self {
             method: None,
             path: None,
+            query: None,
             version: None,
             headers: Header::new(),
             body: None,
+            params: Vec::new(),
+            state: Arc::default(),
+            keys: None,
+            session: None,
+            handler: None,
+            body_reader: None,
         }
     }
 
@@ -32,12 +67,19 @@ impl Request {
         let mut request_line = req_ln.split_whitespace();
 
         req.method = Method::from(request_line.next().unwrap());
-        req.path = request_line.next().unwrap().to_string().into();
-        req.version = request_line.next().unwrap().to_string().into();
+        let target = request_line.next().unwrap();
+        match target.split_once('?') {
+            Some((path, query)) => {
+                req.path = Some(path.to_string());
+                req.query = Some(query.to_string());
+            }
+            None => req.path = Some(target.to_string()),
+        }
+        req.version = request_line.next().and_then(Version::from);
 
         // Extract headers
         let mut headers = Vec::new();
-        while let Some(line) = lines.next() {
+        for line in lines.by_ref() {
             if line.trim().is_empty() {
                 break;
             }
@@ -56,4 +98,486 @@ impl Request {
 
         req
     }
+
+    // Reads a request off `socket`: the head, then the body `Content-Length`
+    // or `Transfer-Encoding: chunked` delimits, with any `Content-Encoding`
+    // undone. The body may not exceed `limit` bytes before or after
+    // decoding. Fails with `UnexpectedEof` when the client sent nothing,
+    // `FileTooLarge` and `Unsupported` for bodies that are too large or use
+    // an unknown coding, `InvalidData` for anything malformed and with the
+    // socket's own error if it times out partway.
+    //
+    // `multipart/form-data` bodies are left on the socket for
+    // `MultipartParser`, which enforces its own limits as it reads them.
+    // Whatever is still unread once middleware has run is read by
+    // `read_body`.
+    pub(crate) fn read<R: Read + Send + 'static>(mut socket: R, limit: usize) -> Result<Self> {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 1024];
+
+        let (head_end, body_start) = loop {
+            if let Some(end) = head_end(&buf) {
+                break end;
+            }
+            if buf.len() > MAX_HEAD_SIZE {
+                return Err(Error::new(ErrorKind::InvalidData, "request head too large"));
+            }
+
+            let n = match socket.read(&mut chunk) {
+                Ok(n) => n,
+                // Connections that are opened ahead of time and then left
+                // idle, or reset, aren't worth an answer.
+                Err(_) if buf.is_empty() => return Err(ErrorKind::UnexpectedEof.into()),
+                Err(err) => return Err(err),
+            };
+            if n == 0 {
+                if buf.is_empty() {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+                break (buf.len(), buf.len());
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        };
+
+        let received = buf.split_off(body_start);
+        let mut head = String::from_utf8_lossy(&buf[..head_end]);
+
+        // `parse` needs at least a method and a target.
+        let request_line = head.lines().next().unwrap_or_default();
+        if request_line.split_whitespace().count() < 2 {
+            return Err(Error::new(ErrorKind::InvalidData, "malformed request line"));
+        }
+        let mut req = Self::parse(&mut head);
+
+        // A transfer coding overrides any `Content-Length`.
+        let transfer = req.headers.get("Transfer-Encoding").cloned();
+        let length = match (&transfer, req.headers.get("Content-Length")) {
+            (Some(_), _) => None,
+            (None, Some(length)) => Some(
+                length
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| Error::new(ErrorKind::InvalidData, "invalid Content-Length"))?,
+            ),
+            (None, None) => Some(0),
+        };
+        if length == Some(0) {
+            return Ok(req);
+        }
+
+        let streamed = req
+            .content_type()
+            .is_some_and(|content_type| content_type.is("multipart/form-data"));
+        if length.is_some_and(|length| length > limit as u64) && !streamed {
+            return Err(decode::too_large());
+        }
+
+        let received = Cursor::new(received).chain(socket);
+        let mut body: BodyReader = match (transfer, length) {
+            (Some(codings), _) => {
+                // The length is only known once the last chunk arrives.
+                req.headers.remove("Transfer-Encoding");
+                req.headers.remove("Content-Length");
+                decode::dechunk(received, &codings)?
+            }
+            (None, length) => Box::new(Exact {
+                inner: received,
+                remaining: length.unwrap_or_default(),
+            }),
+        };
+        if let Some(codings) = req.headers.get("Content-Encoding").cloned() {
+            body = decode::decoder(body, &codings)?;
+            // Later stages see the body as if it had been sent plain.
+            req.headers.remove("Content-Encoding");
+            req.headers.remove("Content-Length");
+        }
+        req.body_reader = Some(Arc::new(Mutex::new(body)));
+
+        if !streamed {
+            req.read_body(limit)?;
+        }
+
+        Ok(req)
+    }
+
+    // Reads whatever of the body `read` left on the socket into `body`, up
+    // to `limit` bytes.
+    pub(crate) fn read_body(&mut self, limit: usize) -> Result<()> {
+        let reader = match self.body_reader.take() {
+            Some(reader) => reader,
+            None => return Ok(()),
+        };
+        let bytes = decode::read_limited(&mut *reader.lock().unwrap(), limit)?;
+
+        if self.headers.get("Content-Length").is_none() {
+            self.headers.set("Content-Length", &bytes.len().to_string());
+        }
+        if !bytes.is_empty() {
+            self.body = Some(Body::from_bytes(bytes));
+        }
+
+        Ok(())
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.headers.content_type()
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.content_length()
+    }
+
+    pub fn accept(&self) -> Vec<QualityItem> {
+        self.headers.accept()
+    }
+
+    pub fn accept_encoding(&self) -> Vec<QualityItem> {
+        self.headers.accept_encoding()
+    }
+
+    pub fn authorization(&self) -> Option<Authorization> {
+        self.headers.authorization()
+    }
+
+    pub fn if_none_match(&self) -> Option<IfNoneMatch> {
+        self.headers.if_none_match()
+    }
+
+    pub fn if_modified_since(&self) -> Option<SystemTime> {
+        self.headers.if_modified_since()
+    }
+
+    pub fn if_match(&self) -> Option<IfMatch> {
+        self.headers.if_match()
+    }
+
+    pub fn if_unmodified_since(&self) -> Option<SystemTime> {
+        self.headers.if_unmodified_since()
+    }
+
+    pub fn range(&self) -> Option<Range> {
+        self.headers.range()
+    }
+
+    pub fn cookies(&self) -> HashMap<String, String> {
+        self.headers.cookie().into_iter().collect()
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.headers
+            .cookie()
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn jar(&self) -> CookieJar {
+        CookieJar::new(self.cookies(), self.keys.clone())
+    }
+
+    // The session loaded by `middleware::Session`, if it is in use.
+    pub fn session(&self) -> Option<Session> {
+        self.session.clone()
+    }
+
+    pub fn host(&self) -> Option<Host> {
+        self.headers.host()
+    }
+}
+
+// A connection that can only be read from until a deadline, however
+// little the client sends at a time. Later reads fail with `TimedOut`.
+pub(crate) struct Deadline {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Deadline {
+    pub(crate) fn new(stream: TcpStream, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now().checked_add(timeout),
+        }
+    }
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let left = match self.deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => return self.stream.read(buf),
+        };
+        if left.is_zero() {
+            return Err(Error::new(ErrorKind::TimedOut, "request not read in time"));
+        }
+
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+// Where the head ends and the body begins: at the first empty line, with
+// lines ended by CRLF or a bare LF.
+fn head_end(buf: &[u8]) -> Option<(usize, usize)> {
+    (0..buf.len()).find_map(|i| match &buf[i..] {
+        [b'\n', b'\n', ..] => Some((i + 1, i + 2)),
+        [b'\n', b'\r', b'\n', ..] => Some((i + 1, i + 3)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+
+    const LIMIT: usize = 64 * 1024;
+
+    fn read(raw: &[u8]) -> Result<Request> {
+        Request::read(Cursor::new(raw.to_vec()), LIMIT)
+    }
+
+    fn post(headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut raw = format!("POST /upload HTTP/1.1\r\n{headers}\r\n\r\n").into_bytes();
+        raw.extend_from_slice(body);
+        raw
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn kind(raw: &[u8]) -> ErrorKind {
+        read(raw)
+            .err()
+            .expect("request should have been rejected")
+            .kind()
+    }
+
+    #[test]
+    fn reads_the_whole_body() {
+        let body: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let mut raw = post("Content-Length: 5000", &body);
+        // Anything past the announced length isn't part of this request.
+        raw.extend_from_slice(b"GET /next HTTP/1.1\r\n\r\n");
+
+        let req = read(&raw).unwrap();
+        assert_eq!(req.path.as_deref(), Some("/upload"));
+        assert_eq!(req.body.unwrap().bytes, body);
+
+        let req = read(b"GET /?a=1 HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        assert_eq!(req.query.as_deref(), Some("a=1"));
+        assert!(req.body.is_none());
+    }
+
+    #[test]
+    fn decodes_compressed_bodies() {
+        let json = br#"{"items":[1,2,3]}"#.repeat(100);
+        let compressed = gzip(&json);
+        let headers = format!(
+            "Content-Encoding: gzip\r\nContent-Length: {}",
+            compressed.len()
+        );
+
+        let req = read(&post(&headers, &compressed)).unwrap();
+        assert_eq!(req.body.as_ref().unwrap().bytes, json);
+        assert_eq!(req.headers.get("Content-Encoding"), None);
+        assert_eq!(req.content_length(), Some(json.len() as u64));
+    }
+
+    #[test]
+    fn rejects_decompression_bombs() {
+        // Ten megabytes of zeros shrink to a few kilobytes.
+        let bomb = gzip(&vec![0; 10 * 1024 * 1024]);
+        assert!(bomb.len() < LIMIT);
+
+        let headers = format!("Content-Encoding: gzip\r\nContent-Length: {}", bomb.len());
+        assert_eq!(kind(&post(&headers, &bomb)), ErrorKind::FileTooLarge);
+
+        // Exactly at the limit is fine.
+        let full = gzip(&vec![0; LIMIT]);
+        let headers = format!("Content-Encoding: gzip\r\nContent-Length: {}", full.len());
+        assert_eq!(
+            read(&post(&headers, &full))
+                .unwrap()
+                .body
+                .unwrap()
+                .bytes
+                .len(),
+            LIMIT
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_and_truncated_bodies() {
+        let headers = format!("Content-Length: {}", LIMIT + 1);
+        assert_eq!(kind(&post(&headers, b"")), ErrorKind::FileTooLarge);
+
+        assert_eq!(
+            kind(&post("Content-Length: 100", b"only a little")),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(&post("Content-Length: ten", b"0123456789")),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(&post("Content-Length: -1", b"")),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn rejects_bad_encodings() {
+        assert_eq!(
+            kind(&post("Content-Encoding: br\r\nContent-Length: 1", b"x")),
+            ErrorKind::Unsupported
+        );
+
+        let truncated = gzip(b"some text");
+        let truncated = &truncated[..truncated.len() - 6];
+        let headers = format!(
+            "Content-Encoding: gzip\r\nContent-Length: {}",
+            truncated.len()
+        );
+        assert_eq!(kind(&post(&headers, truncated)), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn leaves_multipart_bodies_unread() {
+        // Larger than `LIMIT`, which is for `MultipartParser` to judge.
+        let body = vec![b'x'; LIMIT * 2];
+        let headers = format!(
+            "Content-Type: multipart/form-data; boundary=b\r\nContent-Length: {}",
+            body.len()
+        );
+
+        let mut req = read(&post(&headers, &body)).unwrap();
+        assert!(req.body.is_none());
+        assert!(req.body_reader.is_some());
+
+        // Once middleware had its turn, the limit applies as usual.
+        let err = req.read_body(LIMIT).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+
+        let mut req = read(&post(&headers, &body)).unwrap();
+        req.read_body(LIMIT * 2).unwrap();
+        assert_eq!(req.body.unwrap().bytes, body);
+    }
+
+    #[test]
+    fn rejects_malformed_heads() {
+        assert_eq!(kind(b""), ErrorKind::UnexpectedEof);
+        assert_eq!(kind(b"\r\n\r\n"), ErrorKind::InvalidData);
+        assert_eq!(kind(b"GET\r\n\r\n"), ErrorKind::InvalidData);
+        assert_eq!(kind(b"   \r\nHost: x\r\n\r\n"), ErrorKind::InvalidData);
+        assert_eq!(
+            kind(&vec![b'a'; MAX_HEAD_SIZE + 2048]),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn accepts_bare_line_feeds() {
+        let req = read(b"GET /hi HTTP/1.1\nHost: x\n\n").unwrap();
+        assert_eq!(req.path.as_deref(), Some("/hi"));
+        assert_eq!(req.headers.get("Host").map(String::as_str), Some("x"));
+
+        let req = read(b"POST / HTTP/1.1\nContent-Length: 4\r\n\nbody").unwrap();
+        assert_eq!(req.body.unwrap().bytes, b"body");
+    }
+
+    #[test]
+    fn decodes_chunked_bodies() {
+        let raw = post(
+            "Transfer-Encoding: chunked",
+            b"5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nX-Trailer: 1\r\n\r\n",
+        );
+        let req = read(&raw).unwrap();
+        assert_eq!(req.body.as_ref().unwrap().bytes, b"hello, world");
+        assert_eq!(req.headers.get("Transfer-Encoding"), None);
+        assert_eq!(req.content_length(), Some(12));
+
+        // Chunked wins over a `Content-Length` sent alongside it.
+        let raw = post(
+            "Content-Length: 2\r\nTransfer-Encoding: chunked",
+            b"3\nabc\n0\n\n",
+        );
+        assert_eq!(read(&raw).unwrap().body.unwrap().bytes, b"abc");
+
+        let compressed = gzip(b"zipped");
+        let mut body = format!("{:x}\r\n", compressed.len()).into_bytes();
+        body.extend_from_slice(&compressed);
+        body.extend_from_slice(b"\r\n0\r\n\r\n");
+        let raw = post("Transfer-Encoding: gzip, chunked", &body);
+        assert_eq!(read(&raw).unwrap().body.unwrap().bytes, b"zipped");
+    }
+
+    #[test]
+    fn rejects_bad_chunked_bodies() {
+        let chunked = |body: &[u8]| kind(&post("Transfer-Encoding: chunked", body));
+
+        assert_eq!(chunked(b"5\r\nhel"), ErrorKind::InvalidData);
+        assert_eq!(chunked(b"5\r\nhello0\r\n\r\n"), ErrorKind::InvalidData);
+        assert_eq!(chunked(b"zz\r\nhello\r\n0\r\n\r\n"), ErrorKind::InvalidData);
+        assert_eq!(chunked(b"+5\r\nhello\r\n0\r\n\r\n"), ErrorKind::InvalidData);
+        assert_eq!(chunked(b"5\r\nhello\r\n"), ErrorKind::InvalidData);
+
+        let mut large = format!("{:x}\r\n", LIMIT + 1).into_bytes();
+        large.extend(vec![b'x'; LIMIT + 1]);
+        large.extend_from_slice(b"\r\n0\r\n\r\n");
+        assert_eq!(chunked(&large), ErrorKind::FileTooLarge);
+
+        assert_eq!(
+            kind(&post("Transfer-Encoding: gzip", b"x")),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(&post(
+                "Transfer-Encoding: br, chunked",
+                b"1\r\nx\r\n0\r\n\r\n"
+            )),
+            ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn deadline_covers_the_whole_request() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Sends a byte every 20 ms, each well within the deadline.
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            for byte in b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n" {
+                if stream.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let started = Instant::now();
+        let deadline = Deadline::new(socket, Duration::from_millis(200));
+        let err = Request::read(deadline, LIMIT).err().unwrap();
+
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::TimedOut | ErrorKind::WouldBlock
+        ));
+        assert!(started.elapsed() < Duration::from_millis(500));
+        client.join().unwrap();
+    }
 }
@@ -0,0 +1,204 @@
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use std::io::{BufRead, BufReader, Cursor, Error, ErrorKind, Read, Result};
+
+// Chunk sizes and trailers longer than this are rejected.
+const MAX_CHUNK_LINE: usize = 8 * 1024;
+
+pub(super) type BodyReader = Box<dyn Read + Send>;
+
+// Wraps `body` in decoders undoing the codings listed in a
+// `Content-Encoding` header, the last one applied first. Fails with
+// `Unsupported` for codings other than gzip and deflate; corrupt input
+// surfaces as `InvalidData` or `InvalidInput` once the body is read.
+pub(super) fn decoder(mut body: BodyReader, codings: &str) -> Result<BodyReader> {
+    for coding in codings.split(',').rev() {
+        body = match coding.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => Box::new(Decoded(MultiGzDecoder::new(body))),
+            "deflate" => deflate(body)?,
+            other => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("unsupported content coding {other:?}"),
+                ))
+            }
+        };
+    }
+
+    Ok(body)
+}
+
+// Undoes a `Transfer-Encoding`, then any codings applied before chunked.
+// A body whose last coding isn't chunked has no known end and fails with
+// `InvalidData`.
+pub(super) fn dechunk(body: impl Read + Send + 'static, codings: &str) -> Result<BodyReader> {
+    let (rest, last) = codings.rsplit_once(',').unwrap_or(("", codings));
+    if !last.trim().eq_ignore_ascii_case("chunked") {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "transfer coding doesn't end in chunked",
+        ));
+    }
+
+    let chunked = Chunked {
+        inner: BufReader::new(body),
+        remaining: 0,
+        started: false,
+        done: false,
+    };
+    decoder(Box::new(chunked), rest)
+}
+
+// Some clients send raw deflate data under this name, so the first two
+// bytes decide whether it is wrapped in zlib.
+fn deflate(mut body: BodyReader) -> Result<BodyReader> {
+    let mut head = Vec::new();
+    body.by_ref().take(2).read_to_end(&mut head)?;
+
+    let zlib = is_zlib(&head);
+    let body = Cursor::new(head).chain(body);
+    Ok(if zlib {
+        Box::new(Decoded(ZlibDecoder::new(body)))
+    } else {
+        Box::new(Decoded(DeflateDecoder::new(body)))
+    })
+}
+
+fn is_zlib(body: &[u8]) -> bool {
+    matches!(body, [cmf, flg, ..]
+        if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0)
+}
+
+// A truncated stream surfaces as `UnexpectedEof`, which would pass for a
+// client that sent nothing at all.
+struct Decoded<R>(R);
+
+impl<R: Read> Read for Decoded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.0.read(buf).map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => Error::new(ErrorKind::InvalidData, err),
+            _ => err,
+        })
+    }
+}
+
+// Yields exactly the `Content-Length` bytes of a body, failing if the
+// connection ends before they all arrive.
+pub(super) struct Exact<R> {
+    pub(super) inner: R,
+    pub(super) remaining: u64,
+}
+
+impl<R: Read> Read for Exact<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let max = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "body shorter than its Content-Length",
+            ));
+        }
+
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+// Yields the data of a chunked body, skipping chunk extensions and
+// trailers.
+struct Chunked<R> {
+    inner: BufReader<R>,
+    // What is left of the current chunk.
+    remaining: u64,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read> Chunked<R> {
+    // Reads a line, without its line ending.
+    fn line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        self.inner
+            .by_ref()
+            .take(MAX_CHUNK_LINE as u64)
+            .read_until(b'\n', &mut line)?;
+
+        match line.strip_suffix(b"\n") {
+            Some(line) => {
+                Ok(String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned())
+            }
+            None => Err(malformed_chunk()),
+        }
+    }
+}
+
+impl<R: Read> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            // Every chunk's data ends in a line break of its own.
+            if self.started && !self.line()?.is_empty() {
+                return Err(malformed_chunk());
+            }
+            self.started = true;
+
+            let line = self.line()?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return Err(malformed_chunk());
+            }
+            self.remaining = u64::from_str_radix(size, 16).map_err(|_| malformed_chunk())?;
+
+            if self.remaining == 0 {
+                while !self.line()?.is_empty() {}
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let max = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(malformed_chunk());
+        }
+
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn malformed_chunk() -> Error {
+    Error::new(ErrorKind::InvalidData, "malformed chunked body")
+}
+
+// Reads at most one byte past the limit, so a small body that expands
+// enormously never gets the chance to.
+pub(super) fn read_limited(body: &mut dyn Read, limit: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    body.take(limit as u64 + 1).read_to_end(&mut bytes)?;
+
+    if bytes.len() > limit {
+        return Err(too_large());
+    }
+
+    Ok(bytes)
+}
+
+pub(super) fn too_large() -> Error {
+    Error::new(
+        ErrorKind::FileTooLarge,
+        "request body exceeds the size limit",
+    )
+}
@@ -1,15 +1,46 @@
+use super::cookie::{Cookie, Keys};
+use super::header::typed::fmt_http_date;
 use super::header::Header;
+use super::method::Method;
+use super::mime::MimeTypes;
+use super::random;
 use super::status::Status;
-use std::fs;
-use std::io::{Result, Write};
+use super::version::Version;
+use compress::{Compress, Encoder};
+use range::Ranges;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Error, Read, Result, Seek, Write};
 use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+pub(crate) mod compress;
+mod conditional;
+pub mod into_response;
+mod range;
+pub mod redirect;
+
+pub use into_response::IntoResponse;
+pub use redirect::{Redirect, RedirectKind};
 
 pub struct Response {
     socket: TcpStream,
     pub headers: Header,
     pub status: Status,
+    pub(crate) keys: Option<Arc<Keys>>,
+    pub(crate) mime_types: Arc<MimeTypes>,
+    pub(crate) unsent_status: u16,
+    pub(crate) version: Version,
+    pub(crate) request_headers: Header,
+    pub(crate) request_method: Option<Method>,
+    pub(crate) compress: Option<Compress>,
+    hooks: Vec<Hook>,
+    sent: bool,
 }
 
+type Hook = Box<dyn FnOnce(&mut Response) -> Result<()> + Send>;
+
 impl Response {
     pub fn new(socket: TcpStream) -> Self {
         let mut headers = Header::new();
@@ -19,50 +50,543 @@ impl Response {
             socket,
             headers,
             status: Status::new(),
+            keys: None,
+            mime_types: Arc::new(MimeTypes::new()),
+            unsent_status: 500,
+            version: Version::Http11,
+            request_headers: Header::new(),
+            request_method: None,
+            compress: None,
+            hooks: Vec::new(),
+            sent: false,
         }
     }
 
-    pub fn send(&mut self, msg: &str) -> Result<()> {
-        self.headers
-            .set("Content-Length", msg.len().to_string().as_str());
+    // Registers a callback that runs right before the head is written.
+    pub fn before_send(&mut self, hook: impl FnOnce(&mut Response) -> Result<()> + Send + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
 
-        let response = format!(
-            "{}\r\n{}\r\n{}",
-            self.status.to_string(),
-            self.headers.to_string(),
-            msg
-        );
-        self.socket.write(response.as_bytes())?;
+    fn run_hooks(&mut self) -> Result<()> {
+        for hook in std::mem::take(&mut self.hooks) {
+            hook(self)?;
+        }
+
+        Ok(())
+    }
+
+    // Fails with `InvalidInput` for cookies that don't pass `Cookie::validate`.
+    pub fn set_cookie(&mut self, cookie: Cookie) -> Result<()> {
+        cookie.validate()?;
+        self.headers.append("Set-Cookie", &cookie.to_string());
         Ok(())
     }
 
+    pub fn remove_cookie(&mut self, name: &str) -> Result<()> {
+        self.set_cookie(Cookie::removal(name))
+    }
+
+    pub fn set_signed_cookie(&mut self, mut cookie: Cookie) -> Result<()> {
+        cookie.value = self.keys()?.sign(&cookie.name, &cookie.value);
+        self.set_cookie(cookie)
+    }
+
+    pub fn set_private_cookie(&mut self, mut cookie: Cookie) -> Result<()> {
+        cookie.value = self.keys()?.encrypt(&cookie.name, &cookie.value)?;
+        self.set_cookie(cookie)
+    }
+
+    fn keys(&self) -> Result<Arc<Keys>> {
+        self.keys
+            .clone()
+            .ok_or_else(|| Error::other("no cookie keys configured, see Server::cookie_keys"))
+    }
+
+    pub fn status(&mut self, status_code: u16) -> &mut Self {
+        self.status.status_code = status_code;
+        self.status.reason = None;
+        self
+    }
+
+    pub fn json<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let body = serde_json::to_string(value)?;
+        self.headers.set("Content-Type", "application/json");
+        self.send(&body)
+    }
+
+    pub fn redirect(&mut self, location: &str, kind: RedirectKind) -> Result<()> {
+        let location = redirect::encode_location(location);
+        self.headers.set("Location", &location);
+        self.status(kind.status_code())
+            .text(&format!("Redirecting to {location}"))
+    }
+
+    pub fn html(&mut self, body: &str) -> Result<()> {
+        self.headers.set("Content-Type", "text/html; charset=utf-8");
+        self.send(body)
+    }
+
+    pub fn text(&mut self, body: &str) -> Result<()> {
+        self.headers
+            .set("Content-Type", "text/plain; charset=utf-8");
+        self.send(body)
+    }
+
+    // The protocol version the response is written with, matching the request.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn is_sent(&self) -> bool {
+        self.sent
+    }
+
+    pub fn send(&mut self, msg: &str) -> Result<()> {
+        self.write(msg.as_bytes())
+    }
+
     pub fn send_file(&mut self, path: &str) -> Result<()> {
-        let content = fs::read_to_string(path)?;
-
-        // Determine MIME type from file extension
-        let mime_type = match path.split('.').last().unwrap().trim().into() {
-            Some("html") => "text/html",
-            Some("css") => "text/css",
-            Some("js") => "application/javascript",
-            Some("json") => "application/json",
-            Some("png") => "image/png",
-            Some("jpg") | Some("jpeg") => "image/jpeg",
-            Some("gif") => "image/gif",
-            _ => "application/octet-stream",
+        self.send_file_as(path, path, None)
+    }
+
+    // Sends `path` as a copy of `original` compressed with `encoding`, so
+    // it gets the original's content type.
+    pub(crate) fn send_file_as(
+        &mut self,
+        path: &str,
+        original: &str,
+        encoding: Option<&str>,
+    ) -> Result<()> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified().ok();
+
+        self.send_contents(file, metadata.len(), modified, original, encoding)
+    }
+
+    // Sends `len` bytes of file contents, read from wherever they are kept,
+    // with validators derived from `modified` and the type of `path`.
+    pub(crate) fn send_contents(
+        &mut self,
+        mut contents: impl Read + Seek,
+        len: u64,
+        modified: Option<SystemTime>,
+        path: &str,
+        encoding: Option<&str>,
+    ) -> Result<()> {
+        let mut etag = conditional::file_etag(len, modified);
+        if let Some(encoding) = encoding {
+            etag.tag = format!("{}-{encoding}", etag.tag);
+        }
+
+        self.headers.set("ETag", &etag.to_string());
+        if let Some(modified) = modified {
+            self.headers.set("Last-Modified", &fmt_http_date(modified));
+        }
+
+        // Only a successful response is worth validating; a custom 404 page
+        // sent with `send_file` must not turn into a 304.
+        if self.status.status_code == 200 {
+            let outcome = conditional::evaluate(
+                &self.request_headers,
+                self.request_method.as_ref(),
+                &etag,
+                modified,
+            );
+
+            match outcome {
+                Some(304) => return self.status(304).write_empty(),
+                Some(status_code) => {
+                    return self.status(status_code).text(&Status::phrase(status_code))
+                }
+                None => {}
+            }
+        }
+
+        let head = if self.mime_types.sniffing() && encoding.is_none() {
+            let mut head = Vec::with_capacity(512);
+            contents.by_ref().take(512).read_to_end(&mut head)?;
+            contents.rewind()?;
+            Some(head)
+        } else {
+            None
         };
+        let mime_type = self.mime_types.for_path(path, head.as_deref());
+
+        self.headers.set("Content-Type", &mime_type);
+        self.headers.set("Accept-Ranges", "bytes");
+        if let Some(encoding) = encoding {
+            self.headers.set("Content-Encoding", encoding);
+        }
+
+        if self.status.status_code == 200 {
+            let ranges = range::requested(
+                &self.request_headers,
+                self.request_method.as_ref(),
+                &etag,
+                modified,
+                len,
+            );
+
+            match ranges {
+                Ranges::Full => {}
+                Ranges::Unsatisfiable => {
+                    self.headers.set("Content-Range", &format!("bytes */{len}"));
+                    self.headers.remove("Content-Encoding");
+                    return self.status(416).text(&Status::phrase(416));
+                }
+                Ranges::Partial(bounds) => {
+                    self.status(206);
+
+                    if let [(start, end)] = bounds[..] {
+                        self.headers
+                            .set("Content-Range", &format!("bytes {start}-{end}/{len}"));
+                        return self.write_sized(end - start + 1, |socket| {
+                            range::copy(&mut contents, (start, end), socket)
+                        });
+                    }
+
+                    let boundary = random::hex(16)?;
+                    let size = range::multipart_len(&bounds, len, &mime_type, &boundary);
+                    self.headers.set(
+                        "Content-Type",
+                        &format!("multipart/byteranges; boundary={boundary}"),
+                    );
+                    return self.write_sized(size, |socket| {
+                        range::multipart(&mut contents, &bounds, len, &mime_type, &boundary, socket)
+                    });
+                }
+            }
+        }
+
+        // Compressed, the size is only known once it has all been sent.
+        let encoder = match self.compress.take() {
+            Some(compress) => {
+                compress.encoder(&mut self.headers, self.status.status_code, Some(len))
+            }
+            None => None,
+        };
+        if let Some(encoder) = encoder {
+            let mut writer = self.stream_with(Some(encoder))?;
+            io::copy(&mut contents.take(len), &mut writer)?;
+            return writer.finish();
+        }
+
+        if len == 0 {
+            return self.write(&[]);
+        }
+        self.write_sized(len, |socket| {
+            range::copy(&mut contents, (0, len - 1), socket)
+        })
+    }
+
+    // Sends the head now and returns a writer for a body of unknown length,
+    // chunked for HTTP/1.1 clients and ended by closing the connection for
+    // HTTP/1.0 ones.
+    pub fn stream(&mut self) -> Result<BodyWriter<'_>> {
+        let encoder = match self.compress.take() {
+            Some(compress) => compress.stream(&mut self.headers, self.status.status_code),
+            None => None,
+        };
+
+        self.stream_with(encoder)
+    }
+
+    fn stream_with(&mut self, encoder: Option<Encoder>) -> Result<BodyWriter<'_>> {
+        self.headers.remove("Content-Length");
+
+        let chunked = self.version.supports_chunked();
+        if chunked {
+            self.headers.set("Transfer-Encoding", "chunked");
+        }
+
+        self.write_head()?;
+
+        Ok(BodyWriter {
+            socket: &mut self.socket,
+            chunked,
+            encoder,
+            finished: false,
+        })
+    }
+
+    // For responses that must not carry a body, such as 304 Not Modified.
+    fn write_empty(&mut self) -> Result<()> {
+        self.headers.remove("Content-Length");
+        self.headers.remove("Content-Type");
+        self.write_head()
+    }
+
+    fn write(&mut self, body: &[u8]) -> Result<()> {
+        let compressed = match self.compress.take() {
+            Some(compress) => compress.body(&mut self.headers, self.status.status_code, body)?,
+            None => None,
+        };
+        let body = compressed.as_deref().unwrap_or(body);
 
-        self.headers.set("Content-Type", mime_type);
         self.headers
-            .set("Content-Length", content.len().to_string().as_str());
+            .set("Content-Length", body.len().to_string().as_str());
+
+        self.write_head()?;
+        self.socket.write_all(body)?;
+        Ok(())
+    }
+
+    // Sends the head for a body of `len` bytes, then has `body` write them
+    // straight to the socket. Never compressed.
+    fn write_sized(
+        &mut self,
+        len: u64,
+        body: impl FnOnce(&mut TcpStream) -> Result<()>,
+    ) -> Result<()> {
+        self.compress = None;
+        self.headers.set("Content-Length", &len.to_string());
+
+        self.write_head()?;
+        body(&mut self.socket)?;
+        self.socket.flush()
+    }
+
+    // A response can only be written once; a second attempt is an error
+    // rather than a second status line on the wire.
+    fn write_head(&mut self) -> Result<()> {
+        if self.sent {
+            return Err(Error::other("response already sent"));
+        }
+        self.status.code()?;
+
+        self.run_hooks()?;
+        self.sent = true;
+
+        // Every connection is closed once its response has been written.
+        self.headers.set("Connection", "close");
+
+        let head = format!("{} {}\r\n{}\r\n", self.version, self.status, self.headers);
+        self.socket.write_all(head.as_bytes())
+    }
+}
+
+pub struct BodyWriter<'a> {
+    socket: &'a mut TcpStream,
+    chunked: bool,
+    encoder: Option<Encoder>,
+    finished: bool,
+}
+
+impl BodyWriter<'_> {
+    // Ends the body. Dropping the writer does the same, ignoring errors.
+    pub fn finish(mut self) -> Result<()> {
+        self.end()
+    }
+
+    fn end(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        if let Some(encoder) = self.encoder.take() {
+            let tail = encoder.finish()?;
+            self.send(&tail)?;
+        }
+
+        if self.chunked {
+            self.socket.write_all(b"0\r\n\r\n")?;
+        }
+        self.socket.flush()
+    }
+
+    // Writes bytes that are ready for the wire, framed as a chunk if needed.
+    fn send(&mut self, buf: &[u8]) -> Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        if self.chunked {
+            write!(self.socket, "{:x}\r\n", buf.len())?;
+            self.socket.write_all(buf)?;
+            self.socket.write_all(b"\r\n")
+        } else {
+            self.socket.write_all(buf)
+        }
+    }
+}
+
+impl Write for BodyWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match &mut self.encoder {
+            Some(encoder) => {
+                let compressed = encoder.write(buf)?;
+                self.send(&compressed)?;
+            }
+            None => self.send(buf)?,
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(encoder) = &mut self.encoder {
+            let compressed = encoder.flush()?;
+            self.send(&compressed)?;
+        }
+
+        self.socket.flush()
+    }
+}
+
+impl Drop for BodyWriter<'_> {
+    fn drop(&mut self) {
+        let _ = self.end();
+    }
+}
+
+// Handlers that return (or fail, or panic) without sending anything would
+// otherwise leave the client with an empty reply.
+impl Drop for Response {
+    fn drop(&mut self) {
+        if self.sent {
+            return;
+        }
+
+        println!("ERROR: handler finished without sending a response");
+
+        self.hooks.clear();
+        self.headers = Header::new();
+        self.headers.set("Content-Type", "text/plain");
+        self.status.status_code = self.unsent_status;
+
+        let body = Status::phrase(self.unsent_status);
+        let _ = self.write(body.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use flate2::read::GzDecoder;
+    use std::cell::Cell;
+    use std::io::{Cursor, SeekFrom};
+    use std::rc::Rc;
+
+    const LEN: usize = 4 * 1024 * 1024;
+
+    // File contents that remember the largest single read asked of them.
+    struct Contents {
+        data: Cursor<Vec<u8>>,
+        largest_read: Rc<Cell<usize>>,
+    }
 
-        let response = format!(
-            "{}\r\n{}\r\n{}",
-            self.status.to_string(),
-            self.headers.to_string(),
-            content
+    impl Read for Contents {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.largest_read
+                .set(self.largest_read.get().max(buf.len()));
+            self.data.read(buf)
+        }
+    }
+
+    impl Seek for Contents {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            self.data.seek(pos)
+        }
+    }
+
+    fn data() -> Vec<u8> {
+        (0..LEN).map(|i| (i % 251) as u8).collect()
+    }
+
+    // Sends the test file for a request with `headers`, returning what went
+    // out and the largest read made from the file.
+    fn send(headers: &[&str], compress: Option<Compress>) -> (testing::Sent, usize) {
+        let largest_read = Rc::new(Cell::new(0));
+        let contents = Contents {
+            data: Cursor::new(data()),
+            largest_read: Rc::clone(&largest_read),
+        };
+
+        let (mut res, client) = testing::response();
+        res.request_headers = Header::from(headers.iter().map(|h| h.to_string()).collect());
+        res.request_method = Some(Method::Get);
+        res.compress = compress;
+
+        // Read concurrently, the socket buffers are far smaller than the file.
+        let reader = std::thread::spawn(move || testing::read(client));
+        res.send_contents(contents, LEN as u64, None, "video.txt", None)
+            .unwrap();
+        drop(res);
+
+        (reader.join().unwrap(), largest_read.get())
+    }
+
+    #[test]
+    fn files_are_streamed() {
+        let (sent, largest_read) = send(&[], None);
+        assert_eq!(sent.status, 200);
+        assert_eq!(
+            sent.header("Content-Length"),
+            Some(LEN.to_string().as_str())
         );
+        assert_eq!(sent.header("Accept-Ranges"), Some("bytes"));
+        assert!(sent.body == data());
+        assert!(largest_read <= 64 * 1024, "{largest_read}");
+    }
 
-        self.socket.write_all(response.as_bytes())?;
-        Ok(())
+    #[test]
+    fn ranges_are_streamed() {
+        let (sent, largest_read) = send(&["Range: bytes=1-"], None);
+        assert_eq!(sent.status, 206);
+        assert_eq!(
+            sent.header("Content-Range"),
+            Some(format!("bytes 1-{}/{LEN}", LEN - 1).as_str())
+        );
+        assert_eq!(
+            sent.header("Content-Length"),
+            Some((LEN - 1).to_string().as_str())
+        );
+        assert!(sent.body == data()[1..]);
+        assert!(largest_read <= 64 * 1024, "{largest_read}");
+
+        let (sent, largest_read) = send(&["Range: bytes=0-9, -2000000"], None);
+        assert_eq!(sent.status, 206);
+        assert_eq!(
+            sent.header("Content-Length"),
+            Some(sent.body.len().to_string().as_str())
+        );
+        let boundary = sent.header("Content-Type").unwrap();
+        let boundary = boundary.split("boundary=").nth(1).unwrap();
+        assert!(sent
+            .body
+            .starts_with(format!("--{boundary}\r\n").as_bytes()));
+        assert!(sent
+            .body
+            .ends_with(format!("--{boundary}--\r\n").as_bytes()));
+        assert!(largest_read <= 64 * 1024, "{largest_read}");
+    }
+
+    #[test]
+    fn compressed_files_are_streamed() {
+        let compress = Compress {
+            encoding: Some("gzip"),
+            level: 1,
+            min_size: 1024,
+            types: None,
+        };
+        let (sent, largest_read) = send(&[], Some(compress));
+        assert_eq!(sent.status, 200);
+        assert_eq!(sent.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(sent.header("Content-Length"), None);
+        assert_eq!(sent.header("Accept-Ranges"), None);
+
+        let mut body = Vec::new();
+        GzDecoder::new(&testing::dechunk(&sent.body)[..])
+            .read_to_end(&mut body)
+            .unwrap();
+        assert!(body == data());
+        assert!(largest_read <= 64 * 1024, "{largest_read}");
     }
 }
@@ -0,0 +1,360 @@
+use crate::header::{EntityTag, Header, QualityItem};
+use crate::mime;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression as Level;
+use std::io::{Result, Write};
+use std::sync::Arc;
+
+// What the compression middleware settled on for a response: the coding
+// the client accepts, if any, and which responses are worth compressing.
+#[derive(Clone)]
+pub(crate) struct Compress {
+    pub(crate) encoding: Option<&'static str>,
+    pub(crate) level: u32,
+    pub(crate) min_size: usize,
+    // Media ranges such as `text/*`; `None` for the built-in list.
+    pub(crate) types: Option<Arc<Vec<QualityItem>>>,
+}
+
+impl Compress {
+    // Compresses a complete body, or returns `None` to send it as it is.
+    pub(crate) fn body(
+        &self,
+        headers: &mut Header,
+        status_code: u16,
+        body: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let mut encoder = match self.encoder(headers, status_code, Some(body.len() as u64)) {
+            Some(encoder) => encoder,
+            None => return Ok(None),
+        };
+
+        let mut compressed = encoder.write(body)?;
+        compressed.extend(encoder.finish()?);
+        Ok(Some(compressed))
+    }
+
+    // An encoder for a streamed body, whose size isn't known up front.
+    pub(crate) fn stream(&self, headers: &mut Header, status_code: u16) -> Option<Encoder> {
+        self.encoder(headers, status_code, None)
+    }
+
+    // An encoder for a body of `size` bytes, if known, when the response is
+    // worth compressing. Headers are updated to match either way.
+    pub(crate) fn encoder(
+        &self,
+        headers: &mut Header,
+        status_code: u16,
+        size: Option<u64>,
+    ) -> Option<Encoder> {
+        if !self.eligible(headers, status_code) {
+            return None;
+        }
+        add_vary(headers);
+
+        let encoding = match self.encoding {
+            Some(encoding) if size.is_none_or(|size| size >= self.min_size as u64) => encoding,
+            _ => return None,
+        };
+
+        mark_encoded(headers, encoding);
+        Some(Encoder::new(encoding, self.level))
+    }
+
+    // Partial and bodiless responses, and ones already encoded, are left
+    // alone.
+    fn eligible(&self, headers: &Header, status_code: u16) -> bool {
+        if matches!(status_code, 100..=199 | 204 | 206 | 304)
+            || headers.get("Content-Encoding").is_some()
+            || headers.get("Content-Range").is_some()
+        {
+            return false;
+        }
+
+        let mime = match headers.content_type() {
+            Some(content_type) => content_type.mime,
+            None => return false,
+        };
+
+        match &self.types {
+            Some(types) => types.iter().any(|range| range.matches(&mime)),
+            None => mime::is_compressible(&mime),
+        }
+    }
+}
+
+pub(crate) enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: &str, level: u32) -> Self {
+        let level = Level::new(level);
+
+        match encoding {
+            "gzip" => Encoder::Gzip(GzEncoder::new(Vec::new(), level)),
+            // The `deflate` coding is the zlib format, not raw deflate.
+            _ => Encoder::Deflate(ZlibEncoder::new(Vec::new(), level)),
+        }
+    }
+
+    // Compresses `buf`, returning whatever output is ready.
+    pub(crate) fn write(&mut self, buf: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(buf)?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(buf)?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    // Forces out everything written so far, for streams that must not lag.
+    pub(crate) fn flush(&mut self) -> Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => {
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Encoder::Deflate(encoder) => {
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
+
+fn add_vary(headers: &mut Header) {
+    let varies = headers.get_all("Vary").iter().any(|value| {
+        value
+            .split(',')
+            .any(|name| name.trim().eq_ignore_ascii_case("accept-encoding"))
+    });
+
+    if !varies {
+        headers.append("Vary", "Accept-Encoding");
+    }
+}
+
+// A compressed body is a different representation: it gets a validator of
+// its own, as precompressed files do, and no byte ranges, which are only
+// ever served of the original.
+fn mark_encoded(headers: &mut Header, encoding: &str) {
+    headers.set("Content-Encoding", encoding);
+    headers.remove("Accept-Ranges");
+
+    let etag = headers.get("ETag").map(|etag| EntityTag::parse(etag));
+    match etag {
+        Some(Some(mut etag)) => {
+            etag.tag = format!("{}-{encoding}", etag.tag);
+            headers.set("ETag", &etag.to_string());
+        }
+        Some(None) => headers.remove("ETag"),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::{Compression, Middleware};
+    use crate::request::Request;
+    use crate::testing;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use std::io::Read;
+
+    fn compress(encoding: Option<&'static str>) -> Compress {
+        Compress {
+            encoding,
+            level: 6,
+            min_size: 100,
+            types: None,
+        }
+    }
+
+    fn headers(content_type: &str) -> Header {
+        Header::from(vec![format!("Content-Type: {content_type}")])
+    }
+
+    fn gunzip(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn min_size_and_eligibility() {
+        let body = vec![b'a'; 100];
+        let gzip = compress(Some("gzip"));
+
+        let mut small = headers("text/html");
+        assert_eq!(gzip.body(&mut small, 200, &body[..99]).unwrap(), None);
+        assert_eq!(small.get("Content-Encoding"), None);
+
+        let mut html = headers("text/html; charset=utf-8");
+        let compressed = gzip.body(&mut html, 200, &body).unwrap().unwrap();
+        assert_eq!(gunzip(&compressed), body);
+        assert_eq!(html.get("Content-Encoding").unwrap(), "gzip");
+
+        for (content_type, status_code) in
+            [("image/png", 200), ("text/html", 206), ("text/html", 304)]
+        {
+            let mut headers = headers(content_type);
+            assert_eq!(gzip.body(&mut headers, status_code, &body).unwrap(), None);
+        }
+
+        let mut encoded = headers("text/html");
+        encoded.set("Content-Encoding", "br");
+        assert_eq!(gzip.body(&mut encoded, 200, &body).unwrap(), None);
+        assert_eq!(encoded.get("Content-Encoding").unwrap(), "br");
+
+        let custom = Compress {
+            types: Some(Arc::new(QualityItem::parse_list(
+                "image/*, Application/X-Custom",
+            ))),
+            ..compress(Some("deflate"))
+        };
+        assert!(custom
+            .body(&mut headers("image/png"), 200, &body)
+            .unwrap()
+            .is_some());
+        assert!(custom
+            .body(&mut headers("application/x-custom"), 200, &body)
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            custom.body(&mut headers("text/html"), 200, &body).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn vary_is_added_once() {
+        let body = vec![b'a'; 100];
+
+        // Even uncompressed responses depend on `Accept-Encoding`.
+        let mut identity = headers("text/html");
+        assert_eq!(
+            compress(None).body(&mut identity, 200, &body).unwrap(),
+            None
+        );
+        assert_eq!(identity.get_all("Vary"), ["Accept-Encoding"]);
+
+        let mut varied = headers("text/html");
+        varied.append("Vary", "Origin, accept-encoding");
+        compress(Some("gzip"))
+            .body(&mut varied, 200, &body)
+            .unwrap();
+        assert_eq!(varied.get_all("Vary"), ["Origin, accept-encoding"]);
+    }
+
+    #[test]
+    fn encoded_variants_get_their_own_validators() {
+        let body = vec![b'a'; 100];
+
+        let mut headers = headers("text/html");
+        headers.set("ETag", "W/\"64-abc\"");
+        headers.set("Accept-Ranges", "bytes");
+        compress(Some("gzip"))
+            .body(&mut headers, 200, &body)
+            .unwrap();
+        assert_eq!(headers.get("ETag").unwrap(), "W/\"64-abc-gzip\"");
+        assert_eq!(headers.get("Accept-Ranges"), None);
+
+        let mut headers = self::headers("text/html");
+        headers.set("ETag", "\"v1\"");
+        compress(Some("deflate")).stream(&mut headers, 200).unwrap();
+        assert_eq!(headers.get("ETag").unwrap(), "\"v1-deflate\"");
+
+        // Left alone when nothing is compressed.
+        let mut headers = self::headers("text/html");
+        headers.set("ETag", "\"v1\"");
+        compress(Some("gzip"))
+            .body(&mut headers, 200, b"tiny")
+            .unwrap();
+        assert_eq!(headers.get("ETag").unwrap(), "\"v1\"");
+    }
+
+    #[test]
+    fn streams_ignore_min_size() {
+        let mut headers = headers("text/plain");
+        let mut encoder = compress(Some("deflate")).stream(&mut headers, 200).unwrap();
+        assert_eq!(headers.get("Content-Encoding").unwrap(), "deflate");
+
+        let mut compressed = encoder.write(b"hello ").unwrap();
+        compressed.extend(encoder.flush().unwrap());
+        compressed.extend(encoder.write(b"world").unwrap());
+        compressed.extend(encoder.finish().unwrap());
+
+        let mut out = String::new();
+        ZlibDecoder::new(&compressed[..])
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "hello world");
+
+        assert!(compress(None).stream(&mut headers, 200).is_none());
+    }
+
+    #[test]
+    fn negotiates_with_the_client() {
+        let body = "compressible ".repeat(200);
+        let send = |accept_encoding: &str| {
+            let mut req = Request::new();
+            req.headers = Header::from(vec![format!("Accept-Encoding: {accept_encoding}")]);
+
+            let (mut res, client) = testing::response();
+            Compression::new().respond(&req, &mut res).unwrap();
+            res.text(&body).unwrap();
+            drop(res);
+            testing::read(client)
+        };
+
+        let sent = send("deflate;q=0.5, gzip");
+        assert_eq!(sent.status, 200);
+        assert_eq!(sent.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(sent.header("Vary"), Some("Accept-Encoding"));
+        assert_eq!(gunzip(&sent.body), body.as_bytes());
+        assert_eq!(
+            sent.header("Content-Length"),
+            Some(sent.body.len().to_string().as_str())
+        );
+
+        let sent = send("gzip;q=0, deflate");
+        assert_eq!(sent.header("Content-Encoding"), Some("deflate"));
+
+        let sent = send("br");
+        assert_eq!(sent.header("Content-Encoding"), None);
+        assert_eq!(sent.body, body.as_bytes());
+    }
+
+    #[test]
+    fn compresses_streamed_responses() {
+        let mut req = Request::new();
+        req.headers = Header::from(vec!["Accept-Encoding: gzip".to_string()]);
+
+        let (mut res, client) = testing::response();
+        Compression::new().respond(&req, &mut res).unwrap();
+        let mut writer = res.stream().unwrap();
+        writer.write_all(b"tiny, ").unwrap();
+        writer.flush().unwrap();
+        writer.write_all(b"but streamed").unwrap();
+        writer.finish().unwrap();
+        drop(res);
+
+        let sent = testing::read(client);
+        assert_eq!(sent.header("Transfer-Encoding"), Some("chunked"));
+        assert_eq!(sent.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(gunzip(&testing::dechunk(&sent.body)), b"tiny, but streamed");
+    }
+}
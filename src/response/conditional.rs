@@ -0,0 +1,184 @@
+use crate::header::{EntityTag, Header};
+use crate::method::Method;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A weak validator built from the file's size and modification time, which
+// is cheap to compute and changes whenever the file is rewritten.
+pub(crate) fn file_etag(len: u64, modified: Option<SystemTime>) -> EntityTag {
+    let modified = modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    EntityTag::weak(&format!(
+        "{:x}-{:x}.{:x}",
+        len,
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ))
+}
+
+// Evaluates the request's preconditions in the order RFC 9110 (13.2.2) asks
+// for, returning the status to answer with instead of the full response.
+pub(crate) fn evaluate(
+    headers: &Header,
+    method: Option<&Method>,
+    etag: &EntityTag,
+    modified: Option<SystemTime>,
+) -> Option<u16> {
+    let modified = modified.map(truncate_to_seconds);
+
+    if let Some(if_match) = headers.if_match() {
+        if !if_match.matches_strong(etag) {
+            return Some(412);
+        }
+    } else if let (Some(since), Some(modified)) = (headers.if_unmodified_since(), modified) {
+        if modified > since {
+            return Some(412);
+        }
+    }
+
+    let is_get = matches!(method, Some(Method::Get) | None);
+
+    if let Some(if_none_match) = headers.if_none_match() {
+        if if_none_match.matches(etag) {
+            return Some(if is_get { 304 } else { 412 });
+        }
+    } else if let (true, Some(since), Some(modified)) =
+        (is_get, headers.if_modified_since(), modified)
+    {
+        if modified <= since {
+            return Some(304);
+        }
+    }
+
+    None
+}
+
+// HTTP dates only carry whole seconds.
+pub(crate) fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => UNIX_EPOCH + std::time::Duration::from_secs(elapsed.as_secs()),
+        Err(_) => time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn headers(lines: &[&str]) -> Header {
+        Header::from(lines.iter().map(|line| line.to_string()).collect())
+    }
+
+    fn date(time: SystemTime) -> String {
+        httpdate::fmt_http_date(time)
+    }
+
+    // A strong tag and a modification time with a fraction of a second.
+    fn validators() -> (EntityTag, SystemTime) {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        (EntityTag::strong("v1"), modified)
+    }
+
+    fn check(lines: &[&str], method: Method) -> Option<u16> {
+        let (etag, modified) = validators();
+        evaluate(&headers(lines), Some(&method), &etag, Some(modified))
+    }
+
+    #[test]
+    fn if_match_wins_over_if_unmodified_since() {
+        let (_, modified) = validators();
+        let before = date(modified - Duration::from_secs(60));
+        let after = date(modified + Duration::from_secs(60));
+
+        // A matching tag passes even though the date alone would fail.
+        let lines = [
+            r#"If-Match: "v1""#,
+            &format!("If-Unmodified-Since: {before}"),
+        ];
+        assert_eq!(check(&lines, Method::Put), None);
+
+        let lines = [
+            r#"If-Match: "v2""#,
+            &format!("If-Unmodified-Since: {after}"),
+        ];
+        assert_eq!(check(&lines, Method::Put), Some(412));
+
+        // Weak tags never match If-Match.
+        let weak = EntityTag::weak("v1");
+        let outcome = evaluate(
+            &headers(&[r#"If-Match: W/"v1""#]),
+            Some(&Method::Put),
+            &weak,
+            Some(modified),
+        );
+        assert_eq!(outcome, Some(412));
+
+        assert_eq!(
+            check(&[&format!("If-Unmodified-Since: {before}")], Method::Put),
+            Some(412)
+        );
+        // Sub-second precision doesn't make the file look newer.
+        assert_eq!(
+            check(
+                &[&format!("If-Unmodified-Since: {}", date(modified))],
+                Method::Put
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn if_none_match_fails_other_methods_with_412() {
+        assert_eq!(check(&[r#"If-None-Match: "v1""#], Method::Get), Some(304));
+        assert_eq!(check(&[r#"If-None-Match: W/"v1""#], Method::Get), Some(304));
+        assert_eq!(check(&["If-None-Match: *"], Method::Get), Some(304));
+        assert_eq!(check(&[r#"If-None-Match: "v1""#], Method::Post), Some(412));
+        assert_eq!(check(&["If-None-Match: *"], Method::Delete), Some(412));
+        assert_eq!(check(&[r#"If-None-Match: "v2""#], Method::Post), None);
+    }
+
+    #[test]
+    fn if_modified_since_is_ignored_alongside_if_none_match() {
+        let (_, modified) = validators();
+        let after = date(modified + Duration::from_secs(60));
+        let before = date(modified - Duration::from_secs(60));
+
+        assert_eq!(
+            check(&[&format!("If-Modified-Since: {after}")], Method::Get),
+            Some(304)
+        );
+        assert_eq!(
+            check(
+                &[&format!("If-Modified-Since: {}", date(modified))],
+                Method::Get
+            ),
+            Some(304)
+        );
+        assert_eq!(
+            check(&[&format!("If-Modified-Since: {before}")], Method::Get),
+            None
+        );
+
+        // A tag that doesn't match means the client's copy is stale, no
+        // matter the date.
+        let lines = [
+            r#"If-None-Match: "v2""#,
+            &format!("If-Modified-Since: {after}"),
+        ];
+        assert_eq!(check(&lines, Method::Get), None);
+
+        // Only GET asks for If-Modified-Since.
+        assert_eq!(
+            check(&[&format!("If-Modified-Since: {after}")], Method::Post),
+            None
+        );
+    }
+
+    #[test]
+    fn preconditions_run_before_cache_validation() {
+        let lines = [r#"If-Match: "v2""#, r#"If-None-Match: "v1""#];
+        assert_eq!(check(&lines, Method::Get), Some(412));
+    }
+}
@@ -0,0 +1,101 @@
+use super::{Redirect, Response};
+use crate::extract::{Json, Rejection};
+use crate::status::StatusCode;
+use serde::Serialize;
+use std::io::{Error, Result};
+
+// Lets handlers return a value instead of writing to a `Response` they own.
+pub trait IntoResponse {
+    fn into_response(self, res: Response) -> Result<()>;
+}
+
+impl IntoResponse for String {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.text(&self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.text(self)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (u16, T) {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.status(self.0);
+        self.1.into_response(res)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.status.set(self.0);
+        self.1.into_response(res)
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.status.set(self);
+        res.send(self.canonical_reason().unwrap_or(""))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.json(&self.0)
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self, res: Response) -> Result<()> {
+        self.send(res)
+    }
+}
+
+// The details stay in the log; the client only learns that something failed.
+impl IntoResponse for Error {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        println!("ERROR: {:?}", self);
+        res.status(500).send("Internal Server Error")
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for std::result::Result<T, E> {
+    fn into_response(self, res: Response) -> Result<()> {
+        match self {
+            Ok(value) => value.into_response(res),
+            Err(err) => err.into_response(res),
+        }
+    }
+}
+
+impl IntoResponse for Redirect {
+    fn into_response(self, mut res: Response) -> Result<()> {
+        res.redirect(&self.location, self.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Sent};
+
+    fn send(value: impl IntoResponse) -> Sent {
+        let (res, client) = testing::response();
+        value.into_response(res).unwrap();
+        testing::read(client)
+    }
+
+    #[test]
+    fn strings_are_sent_as_plain_text() {
+        for sent in [send("hi"), send((201, "hi".to_string()))] {
+            assert_eq!(
+                sent.header("Content-Type"),
+                Some("text/plain; charset=utf-8")
+            );
+            assert_eq!(sent.body, b"hi");
+        }
+    }
+}
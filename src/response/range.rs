@@ -0,0 +1,295 @@
+use super::conditional::truncate_to_seconds;
+use crate::header::typed::parse_http_date;
+use crate::header::{EntityTag, Header};
+use crate::method::Method;
+use std::io::{self, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::time::SystemTime;
+
+// Requests for more ranges than this get the whole file instead.
+const MAX_RANGES: usize = 32;
+
+pub(crate) enum Ranges {
+    Full,
+    Unsatisfiable,
+    Partial(Vec<(u64, u64)>),
+}
+
+pub(crate) fn requested(
+    headers: &Header,
+    method: Option<&Method>,
+    etag: &EntityTag,
+    modified: Option<SystemTime>,
+    len: u64,
+) -> Ranges {
+    if !matches!(method, Some(Method::Get) | None) {
+        return Ranges::Full;
+    }
+
+    // Malformed or unknown ranges are ignored rather than rejected.
+    let range = match headers.range() {
+        Some(range) if range.is_bytes() && range.ranges.len() <= MAX_RANGES => range,
+        _ => return Ranges::Full,
+    };
+
+    if !if_range_allows(headers, etag, modified) {
+        return Ranges::Full;
+    }
+
+    let bounds: Vec<(u64, u64)> = range
+        .ranges
+        .iter()
+        .filter_map(|range| range.to_bounds(len))
+        .collect();
+
+    if bounds.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+
+    // Overlapping ranges would send the same bytes several times over, so
+    // they are merged, and a set that covers the file is just the file.
+    let bounds = coalesce(bounds);
+    let total: u64 = bounds.iter().map(|(start, end)| end - start + 1).sum();
+
+    if total >= len {
+        Ranges::Full
+    } else {
+        Ranges::Partial(bounds)
+    }
+}
+
+// Sorts the bounds and merges any that overlap or touch.
+fn coalesce(mut bounds: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    bounds.sort_unstable();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(bounds.len());
+    for (start, end) in bounds {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= last_end.saturating_add(1) => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+}
+
+// `If-Range` makes the range conditional on the representation being
+// unchanged; both validator kinds require an exact, strong match.
+fn if_range_allows(headers: &Header, etag: &EntityTag, modified: Option<SystemTime>) -> bool {
+    let value = match headers.get("If-Range") {
+        Some(value) => value,
+        None => return true,
+    };
+
+    if let Some(tag) = EntityTag::parse(value) {
+        return tag.strong_eq(etag);
+    }
+
+    match (parse_http_date(value), modified) {
+        (Some(date), Some(modified)) => truncate_to_seconds(modified) == date,
+        _ => false,
+    }
+}
+
+// Copies bytes `start..=end` of `file` to `out` without holding them all.
+pub(crate) fn copy(
+    file: &mut (impl Read + Seek),
+    (start, end): (u64, u64),
+    out: &mut impl Write,
+) -> Result<()> {
+    file.seek(SeekFrom::Start(start))?;
+    let expected = end - start + 1;
+
+    // The length is already on the wire, so a file that shrank since
+    // can only end the response early.
+    if io::copy(&mut file.take(expected), out)? < expected {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "file shrank while sent",
+        ));
+    }
+
+    Ok(())
+}
+
+// Writes a `multipart/byteranges` body of `multipart_len` bytes.
+pub(crate) fn multipart(
+    file: &mut (impl Read + Seek),
+    bounds: &[(u64, u64)],
+    len: u64,
+    mime_type: &str,
+    boundary: &str,
+    out: &mut impl Write,
+) -> Result<()> {
+    for &bound in bounds {
+        out.write_all(part_head(bound, len, mime_type, boundary).as_bytes())?;
+        copy(file, bound, out)?;
+        out.write_all(b"\r\n")?;
+    }
+
+    out.write_all(format!("--{boundary}--\r\n").as_bytes())
+}
+
+pub(crate) fn multipart_len(
+    bounds: &[(u64, u64)],
+    len: u64,
+    mime_type: &str,
+    boundary: &str,
+) -> u64 {
+    let parts: u64 = bounds
+        .iter()
+        .map(|&(start, end)| {
+            let head = part_head((start, end), len, mime_type, boundary);
+            head.len() as u64 + (end - start + 1) + 2
+        })
+        .sum();
+
+    parts + format!("--{boundary}--\r\n").len() as u64
+}
+
+fn part_head((start, end): (u64, u64), len: u64, mime_type: &str, boundary: &str) -> String {
+    format!(
+        "--{boundary}\r\nContent-Type: {mime_type}\r\nContent-Range: bytes {start}-{end}/{len}\r\n\r\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::typed::fmt_http_date;
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const LEN: u64 = 1000;
+
+    fn etag() -> EntityTag {
+        EntityTag::strong("v1")
+    }
+
+    fn modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    fn ranges(headers: &[&str]) -> Ranges {
+        let headers = Header::from(headers.iter().map(|line| line.to_string()).collect());
+        requested(&headers, None, &etag(), Some(modified()), LEN)
+    }
+
+    fn bounds(headers: &[&str]) -> Vec<(u64, u64)> {
+        match ranges(headers) {
+            Ranges::Partial(bounds) => bounds,
+            Ranges::Full => panic!("{headers:?} got the full file"),
+            Ranges::Unsatisfiable => panic!("{headers:?} was unsatisfiable"),
+        }
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(bounds(&["Range: bytes=0-99"]), [(0, 99)]);
+        assert_eq!(bounds(&["Range: bytes=990-5000"]), [(990, 999)]);
+        // Open-ended and suffix ranges.
+        assert_eq!(bounds(&["Range: bytes=900-"]), [(900, 999)]);
+        assert_eq!(bounds(&["Range: bytes=-10"]), [(990, 999)]);
+    }
+
+    #[test]
+    fn ranges_covering_the_file() {
+        assert!(matches!(ranges(&["Range: bytes=0-"]), Ranges::Full));
+        assert!(matches!(ranges(&["Range: bytes=-5000"]), Ranges::Full));
+        assert!(matches!(
+            ranges(&["Range: bytes=0-499, 500-999"]),
+            Ranges::Full
+        ));
+    }
+
+    #[test]
+    fn overlapping_ranges_are_merged() {
+        assert_eq!(
+            bounds(&["Range: bytes=500-599, 0-9, 5-20, 21-30, 550-"]),
+            [(0, 30), (500, 999)]
+        );
+        assert_eq!(bounds(&["Range: bytes=10-19, 10-19, -990"]), [(10, 999)]);
+
+        // Repeating the whole file can't be used to amplify the response.
+        let repeated = format!("Range: bytes={}", vec!["0-"; MAX_RANGES].join(","));
+        assert!(matches!(ranges(&[&repeated]), Ranges::Full));
+        let repeated = format!("Range: bytes={}", vec!["1-"; MAX_RANGES].join(","));
+        assert_eq!(bounds(&[&repeated]), [(1, 999)]);
+    }
+
+    #[test]
+    fn unsatisfiable_and_ignored_ranges() {
+        assert!(matches!(
+            ranges(&["Range: bytes=1000-"]),
+            Ranges::Unsatisfiable
+        ));
+        assert!(matches!(
+            ranges(&["Range: bytes=2000-3000, -0"]),
+            Ranges::Unsatisfiable
+        ));
+        // Unsatisfiable ranges among satisfiable ones are dropped.
+        assert_eq!(bounds(&["Range: bytes=2000-3000, 0-0"]), [(0, 0)]);
+
+        // Malformed, unknown and excessive ranges are ignored.
+        assert!(matches!(ranges(&["Range: bytes=5-1"]), Ranges::Full));
+        assert!(matches!(ranges(&["Range: items=0-1"]), Ranges::Full));
+        let many = format!("Range: bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert!(matches!(ranges(&[&many]), Ranges::Full));
+
+        let headers = Header::from(vec!["Range: bytes=0-0".to_string()]);
+        let ranges = requested(&headers, Some(&Method::Post), &etag(), None, LEN);
+        assert!(matches!(ranges, Ranges::Full));
+    }
+
+    #[test]
+    fn if_range() {
+        let date = fmt_http_date(modified());
+        let earlier = fmt_http_date(modified() - Duration::from_secs(1));
+
+        assert_eq!(bounds(&["Range: bytes=0-0", "If-Range: \"v1\""]), [(0, 0)]);
+        assert_eq!(
+            bounds(&["Range: bytes=0-0", &format!("If-Range: {date}")]),
+            [(0, 0)]
+        );
+
+        for stale in [
+            "If-Range: \"v2\"".to_string(),
+            // Weak tags never match.
+            "If-Range: W/\"v1\"".to_string(),
+            format!("If-Range: {earlier}"),
+            "If-Range: garbage".to_string(),
+        ] {
+            assert!(
+                matches!(ranges(&["Range: bytes=0-0", &stale]), Ranges::Full),
+                "{stale}"
+            );
+        }
+    }
+
+    #[test]
+    fn multipart_body() {
+        let data: Vec<u8> = (0..LEN).map(|i| (i % 256) as u8).collect();
+        let bounds = [(0, 1), (998, 999)];
+        let mut body = Vec::new();
+        multipart(
+            &mut Cursor::new(&data),
+            &bounds,
+            LEN,
+            "text/plain",
+            "sep",
+            &mut body,
+        )
+        .unwrap();
+        assert_eq!(
+            multipart_len(&bounds, LEN, "text/plain", "sep"),
+            body.len() as u64
+        );
+
+        let mut expected = b"--sep\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/1000\r\n\r\n\x00\x01\r\n".to_vec();
+        expected.extend_from_slice(
+            b"--sep\r\nContent-Type: text/plain\r\nContent-Range: bytes 998-999/1000\r\n\r\n\xe6\xe7\r\n--sep--\r\n",
+        );
+        assert_eq!(body, expected);
+    }
+}
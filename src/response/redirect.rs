@@ -0,0 +1,68 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+// Characters that can't appear in a `Location` header as-is. Reserved
+// characters are kept so already formed URLs pass through unchanged.
+const LOCATION: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'|')
+    .add(b'\\')
+    .add(b'^');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    MovedPermanently,
+    Found,
+    SeeOther,
+    Temporary,
+    Permanent,
+}
+
+impl RedirectKind {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            RedirectKind::MovedPermanently => 301,
+            RedirectKind::Found => 302,
+            RedirectKind::SeeOther => 303,
+            RedirectKind::Temporary => 307,
+            RedirectKind::Permanent => 308,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub(crate) kind: RedirectKind,
+    pub(crate) location: String,
+}
+
+impl Redirect {
+    pub fn new(location: &str, kind: RedirectKind) -> Self {
+        Self {
+            kind,
+            location: location.to_string(),
+        }
+    }
+
+    // 303 See Other, e.g. after a form submission.
+    pub fn to(location: &str) -> Self {
+        Self::new(location, RedirectKind::SeeOther)
+    }
+
+    pub fn temporary(location: &str) -> Self {
+        Self::new(location, RedirectKind::Temporary)
+    }
+
+    pub fn permanent(location: &str) -> Self {
+        Self::new(location, RedirectKind::Permanent)
+    }
+}
+
+pub(crate) fn encode_location(location: &str) -> String {
+    utf8_percent_encode(location, LOCATION).to_string()
+}
@@ -1,34 +1,74 @@
+use super::handler::{Callback, Handler};
 use super::method::Method;
-use super::{request::Request, response::Response};
+use percent_encoding::percent_decode_str;
 use std::collections::HashMap;
-use std::io::Result;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Route {
     pub path: String,
     pub method: Method,
-    pub callback: Option<fn(Request, Response) -> Result<()>>,
+    pub callback: Option<Callback>,
 }
 
 impl Route {
-    pub fn new(path: &str, method: Method, callback: fn(Request, Response) -> Result<()>) -> Self {
+    pub fn new<T>(path: &str, method: Method, callback: impl Handler<T>) -> Self {
         Route {
             path: String::from(path),
             method,
-            callback: Some(callback),
+            callback: Some(Arc::new(move |req, res| callback.call(req, res))),
+        }
+    }
+
+    // Matches `path` against this route, where a segment such as `:id`
+    // captures the corresponding segment of the path.
+    pub fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        let mut route_segments = self.path.split('/');
+        let mut path_segments = path.split('/');
+
+        loop {
+            match (route_segments.next(), path_segments.next()) {
+                (Some(route_segment), Some(path_segment)) => {
+                    if let Some(name) = route_segment.strip_prefix(':') {
+                        if path_segment.is_empty() {
+                            return None;
+                        }
+
+                        let value = percent_decode_str(path_segment).decode_utf8().ok()?;
+                        params.push((name.to_string(), value.into_owned()));
+                    } else if route_segment != path_segment {
+                        return None;
+                    }
+                }
+                (None, None) => return Some(params),
+                _ => return None,
+            }
         }
     }
 }
 
-#[derive(Clone)]
+// How a path that only differs from a route by a trailing slash is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    // `/about` and `/about/` are unrelated paths.
+    #[default]
+    Strict,
+    // Redirect to the form the route was registered with.
+    Redirect,
+    // Serve the route under either form.
+    Ignore,
+}
 
+#[derive(Clone, Default)]
 pub struct Router {
     pub get: HashMap<String, Route>,
     pub post: HashMap<String, Route>,
     pub put: HashMap<String, Route>,
     pub patch: HashMap<String, Route>,
     pub delete: HashMap<String, Route>,
-    pub not_found: Option<fn(Request, Response) -> Result<()>>,
+    pub not_found: Option<Callback>,
+    pub trailing_slash: TrailingSlash,
 }
 
 impl Router {
@@ -40,6 +80,7 @@ impl Router {
             patch: HashMap::new(),
             delete: HashMap::new(),
             not_found: None,
+            trailing_slash: TrailingSlash::Strict,
         }
     }
 
@@ -64,12 +105,40 @@ impl Router {
     }
 
     pub fn get(&mut self, method: &Method, path: &str) -> Option<&Route> {
-        match method {
-            Method::Get => self.get.get(path),
-            Method::Post => self.post.get(path),
-            Method::Put => self.put.get(path),
-            Method::Patch => self.patch.get(path),
-            Method::Delete => self.delete.get(path),
+        self.find(method, path).map(|(route, _)| route)
+    }
+
+    // Looks up the route for `path` along with its captured parameters.
+    // Exact matches win, then the pattern with the fewest parameters.
+    pub fn find(&self, method: &Method, path: &str) -> Option<(&Route, Vec<(String, String)>)> {
+        let routes = match method {
+            Method::Get => &self.get,
+            Method::Post => &self.post,
+            Method::Put => &self.put,
+            Method::Patch => &self.patch,
+            Method::Delete => &self.delete,
+        };
+
+        if let Some(route) = routes.get(path) {
+            return Some((route, Vec::new()));
         }
+
+        routes
+            .values()
+            .filter(|route| route.path.contains("/:"))
+            .filter_map(|route| Some((route, route.matches(path)?)))
+            .min_by_key(|(route, params)| (params.len(), route.path.clone()))
+    }
+
+    // The same path with its trailing slash added or removed, if that form
+    // has a route.
+    pub fn alternate(&self, method: &Method, path: &str) -> Option<String> {
+        let alternate = match path.strip_suffix('/') {
+            Some("") => return None,
+            Some(stripped) => stripped.to_string(),
+            None => format!("{path}/"),
+        };
+
+        self.find(method, &alternate).map(|_| alternate)
     }
 }
@@ -0,0 +1,205 @@
+use crate::random;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Error, Result};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+pub mod file;
+pub mod memory;
+
+pub use file::FileStore;
+pub use memory::MemoryStore;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub data: HashMap<String, Value>,
+    pub expires: SystemTime,
+}
+
+impl Record {
+    pub fn is_expired(&self) -> bool {
+        self.expires <= SystemTime::now()
+    }
+}
+
+pub trait SessionStore: Send + Sync {
+    fn load(&self, id: &str) -> Result<Option<Record>>;
+    fn save(&self, id: &str, record: &Record) -> Result<()>;
+    fn destroy(&self, id: &str) -> Result<()>;
+
+    // Drops every expired record, returning how many were removed.
+    fn cleanup(&self) -> Result<usize>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum State {
+    Unchanged,
+    Changed,
+    Destroyed,
+}
+
+#[derive(Debug)]
+pub(crate) struct Inner {
+    pub(crate) id: String,
+    pub(crate) previous_id: Option<String>,
+    pub(crate) data: HashMap<String, Value>,
+    pub(crate) is_new: bool,
+    pub(crate) state: State,
+}
+
+// A handle to the current request's session. Clones share the same data, so
+// changes made by a handler are seen by the middleware when it persists them.
+#[derive(Debug, Clone)]
+pub struct Session {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Session {
+    pub(crate) fn new(id: String, data: HashMap<String, Value>, is_new: bool) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                id,
+                previous_id: None,
+                data,
+                is_new,
+                state: State::Unchanged,
+            })),
+        }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap()
+    }
+
+    pub fn id(&self) -> String {
+        self.lock().id.clone()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.lock().data.get(key)?.clone();
+        serde_json::from_value(value).ok()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.lock().data.contains_key(key)
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<()> {
+        let value = serde_json::to_value(value).map_err(Error::other)?;
+
+        let mut inner = self.lock();
+        inner.data.insert(key.to_string(), value);
+        inner.state = State::Changed;
+
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.lock();
+        if inner.data.remove(key).is_some() {
+            inner.state = State::Changed;
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.data.clear();
+        inner.state = State::Changed;
+    }
+
+    // Moves the data to a fresh ID, e.g. after logging in, so an ID that was
+    // known before authentication can't be used to hijack the session.
+    pub fn regenerate(&self) -> Result<()> {
+        let id = generate_id()?;
+
+        let mut inner = self.lock();
+        if !inner.is_new && inner.previous_id.is_none() {
+            inner.previous_id = Some(inner.id.clone());
+        }
+        inner.id = id;
+        inner.state = State::Changed;
+
+        Ok(())
+    }
+
+    pub fn destroy(&self) {
+        let mut inner = self.lock();
+        inner.data.clear();
+        inner.state = State::Destroyed;
+    }
+}
+
+pub(crate) fn generate_id() -> Result<String> {
+    random::hex(32)
+}
+
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn records_expire() {
+        let mut record = Record {
+            data: HashMap::new(),
+            expires: SystemTime::now() + Duration::from_secs(60),
+        };
+        assert!(!record.is_expired());
+
+        record.expires = SystemTime::now() - Duration::from_secs(1);
+        assert!(record.is_expired());
+    }
+
+    #[test]
+    fn regenerate_keeps_the_data_under_a_new_id() {
+        let session = Session::new(generate_id().unwrap(), HashMap::new(), false);
+        session.insert("user", 7).unwrap();
+        let first = session.id();
+
+        session.regenerate().unwrap();
+        session.regenerate().unwrap();
+
+        let inner = session.lock();
+        assert!(is_valid_id(&inner.id));
+        assert_ne!(inner.id, first);
+        // Only the ID the store knows about has to be destroyed.
+        assert_eq!(inner.previous_id.as_deref(), Some(first.as_str()));
+        assert_eq!(inner.data["user"], 7);
+        assert_eq!(inner.state, State::Changed);
+    }
+
+    #[test]
+    fn regenerating_a_new_session_leaves_nothing_to_destroy() {
+        let session = Session::new(generate_id().unwrap(), HashMap::new(), true);
+        session.regenerate().unwrap();
+        assert_eq!(session.lock().previous_id, None);
+    }
+
+    #[test]
+    fn destroy_drops_the_data() {
+        let session = Session::new(generate_id().unwrap(), HashMap::new(), false);
+        session.insert("user", 7).unwrap();
+
+        session.destroy();
+        assert!(!session.contains("user"));
+        assert_eq!(session.lock().state, State::Destroyed);
+    }
+
+    #[test]
+    fn ids_are_long_hex_strings() {
+        let id = generate_id().unwrap();
+        assert!(is_valid_id(&id));
+        assert_ne!(id, generate_id().unwrap());
+
+        for id in ["", "abc", &"g".repeat(64), &"a".repeat(63), &"a".repeat(65)] {
+            assert!(!is_valid_id(id), "{id}");
+        }
+    }
+}
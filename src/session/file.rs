@@ -0,0 +1,195 @@
+use super::{is_valid_id, Record, SessionStore};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+// Keeps one JSON file per session in `dir`.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self { dir })
+    }
+
+    fn file(&self, id: &str) -> Result<PathBuf> {
+        // IDs come from cookies, so never let one name a path of its own.
+        if !is_valid_id(id) {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid session id"));
+        }
+
+        Ok(self.dir.join(format!("{id}.json")))
+    }
+
+    fn read(path: &PathBuf) -> Result<Option<Record>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let value: Value = serde_json::from_str(&content)?;
+        let expires = value["expires"].as_u64().unwrap_or(0);
+        let data = match value.get("data") {
+            Some(data) => serde_json::from_value(data.clone())?,
+            None => Default::default(),
+        };
+
+        Ok(Some(Record {
+            data,
+            expires: UNIX_EPOCH + Duration::from_secs(expires),
+        }))
+    }
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> Result<Option<Record>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        Ok(Self::read(&self.file(id)?)?.filter(|record| !record.is_expired()))
+    }
+
+    fn save(&self, id: &str, record: &Record) -> Result<()> {
+        let expires = record
+            .expires
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let content = json!({ "expires": expires, "data": record.data });
+
+        // Write to a temporary file first so readers never see half a record.
+        let path = self.file(id)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, content.to_string())?;
+        fs::rename(tmp, path)
+    }
+
+    fn destroy(&self, id: &str) -> Result<()> {
+        match fs::remove_file(self.file(id)?) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn cleanup(&self) -> Result<usize> {
+        let mut removed = 0;
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            let expired = match Self::read(&path) {
+                Ok(Some(record)) => record.is_expired(),
+                Ok(None) => false,
+                Err(_) => true,
+            };
+
+            if expired && fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::generate_id;
+    use std::time::SystemTime;
+
+    fn store(name: &str) -> (FileStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "just_serve_it-{}-sessions-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        (FileStore::new(dir.join("store")).unwrap(), dir)
+    }
+
+    fn record(ttl: i64) -> Record {
+        let now = SystemTime::now();
+        let delta = Duration::from_secs(ttl.unsigned_abs());
+        Record {
+            data: [("user".to_string(), json!(7))].into(),
+            expires: if ttl >= 0 { now + delta } else { now - delta },
+        }
+    }
+
+    #[test]
+    fn saves_loads_and_destroys() {
+        let (store, dir) = store("round-trip");
+        let id = generate_id().unwrap();
+
+        assert_eq!(store.load(&id).unwrap(), None);
+        store.save(&id, &record(60)).unwrap();
+        assert_eq!(store.load(&id).unwrap().unwrap().data["user"], 7);
+
+        store.destroy(&id).unwrap();
+        assert_eq!(store.load(&id).unwrap(), None);
+        // Destroying it twice is fine.
+        store.destroy(&id).unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expired_records_are_not_loaded() {
+        let (store, dir) = store("expired");
+        let id = generate_id().unwrap();
+
+        store.save(&id, &record(-10)).unwrap();
+        assert_eq!(store.load(&id).unwrap(), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ids_never_name_paths() {
+        let (store, dir) = store("traversal");
+        fs::write(dir.join("secret.json"), r#"{"expires":9999999999}"#).unwrap();
+
+        for id in ["../secret", "/etc/passwd", "a/../../b", "..", ""] {
+            assert_eq!(store.load(id).unwrap(), None, "{id}");
+            let err = store.save(id, &record(60)).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{id}");
+            let err = store.destroy(id).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{id}");
+        }
+        assert!(dir.join("secret.json").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cleanup_removes_expired_and_unreadable_records() {
+        let (store, dir) = store("cleanup");
+        let live = generate_id().unwrap();
+        let expired = generate_id().unwrap();
+        let corrupt = generate_id().unwrap();
+
+        store.save(&live, &record(60)).unwrap();
+        store.save(&expired, &record(-10)).unwrap();
+        fs::write(dir.join(format!("store/{corrupt}.json")), "{").unwrap();
+        fs::write(dir.join("store/notes.txt"), "kept").unwrap();
+
+        assert_eq!(store.cleanup().unwrap(), 2);
+        assert!(store.load(&live).unwrap().is_some());
+        assert!(!dir.join(format!("store/{expired}.json")).exists());
+        assert!(!dir.join(format!("store/{corrupt}.json")).exists());
+        assert!(dir.join("store/notes.txt").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use super::{Record, SessionStore};
+use std::collections::HashMap;
+use std::io::Result;
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct MemoryStore {
+    records: Mutex<HashMap<String, Record>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Result<Option<Record>> {
+        let records = self.records.lock().unwrap();
+        Ok(records
+            .get(id)
+            .filter(|record| !record.is_expired())
+            .cloned())
+    }
+
+    fn save(&self, id: &str, record: &Record) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        records.insert(id.to_string(), record.clone());
+        Ok(())
+    }
+
+    fn destroy(&self, id: &str) -> Result<()> {
+        self.records.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    fn cleanup(&self) -> Result<usize> {
+        let mut records = self.records.lock().unwrap();
+        let before = records.len();
+        records.retain(|_, record| !record.is_expired());
+        Ok(before - records.len())
+    }
+}
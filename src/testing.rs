@@ -0,0 +1,133 @@
+use crate::header::Header;
+use crate::middleware::Middleware;
+use crate::request::Request;
+use crate::response::Response;
+use crate::routes::Router;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+
+// What a response put on the wire.
+pub(crate) struct Sent {
+    pub(crate) status: u16,
+    pub(crate) headers: Header,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Sent {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+// A response writing to one end of a loopback connection, and the other
+// end to read it back from with `read`.
+pub(crate) fn response() -> (Response, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (socket, _) = listener.accept().unwrap();
+
+    (Response::new(socket), client)
+}
+
+// Reads everything sent to `client`, once the response has been dropped.
+pub(crate) fn read(mut client: TcpStream) -> Sent {
+    let mut raw = Vec::new();
+    client.read_to_end(&mut raw).unwrap();
+
+    let end = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .expect("no response head");
+    let head = String::from_utf8(raw[..end].to_vec()).unwrap();
+    let mut lines = head.lines();
+    let status = lines.next().unwrap()[9..12].parse().unwrap();
+
+    Sent {
+        status,
+        headers: Header::from(lines.map(str::to_string).collect()),
+        body: raw[end + 4..].to_vec(),
+    }
+}
+
+// Removes the chunk framing from a `Transfer-Encoding: chunked` body.
+pub(crate) fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .unwrap();
+        let size = std::str::from_utf8(&body[..line_end]).unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            return data;
+        }
+
+        body = &body[line_end + 2..];
+        data.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+// Runs `middleware` on a GET for `path` with extra `headers`, and what the
+// handler it picked sent, if it picked one.
+pub(crate) fn get(
+    middleware: &dyn Middleware,
+    routes: &mut Router,
+    path: &str,
+    headers: &[&str],
+) -> Option<Sent> {
+    let mut raw = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n");
+    for header in headers {
+        raw.push_str(&format!("{header}\r\n"));
+    }
+    raw.push_str("\r\n");
+
+    let mut req = Request::read(Cursor::new(raw.into_bytes()), 1024).unwrap();
+    middleware.handle(routes, &mut req).unwrap();
+    let handler = req.handler.take()?;
+
+    let (res, client) = response();
+    handler(req, res).unwrap();
+    Some(read(client))
+}
+
+// A scratch tree:
+//   <dir>/secret.txt
+//   <dir>/public/a.txt
+//   <dir>/public/index.html
+//   <dir>/public/.env
+//   <dir>/public/.git/config
+//   <dir>/public/docs/guide.txt
+//   <dir>/public/inside -> docs/guide.txt
+//   <dir>/public/outside -> ../secret.txt
+//   <dir>/public/env -> .env
+//   <dir>/public/git -> .git
+//   <dir>/public/loop -> .
+pub(crate) fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("just_serve_it-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    fs::create_dir_all(dir.join("public/.git")).unwrap();
+    fs::create_dir_all(dir.join("public/docs")).unwrap();
+    fs::write(dir.join("secret.txt"), "secret").unwrap();
+    fs::write(dir.join("public/a.txt"), "a").unwrap();
+    fs::write(dir.join("public/index.html"), "<h1>home</h1>").unwrap();
+    fs::write(dir.join("public/.env"), "KEY=value").unwrap();
+    fs::write(dir.join("public/.git/config"), "[core]").unwrap();
+    fs::write(dir.join("public/docs/guide.txt"), "guide").unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink("docs/guide.txt", dir.join("public/inside")).unwrap();
+        symlink("../secret.txt", dir.join("public/outside")).unwrap();
+        symlink(".env", dir.join("public/env")).unwrap();
+        symlink(".git", dir.join("public/git")).unwrap();
+        symlink(".", dir.join("public/loop")).unwrap();
+    }
+
+    dir
+}
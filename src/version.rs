@@ -0,0 +1,38 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    pub fn from(version_str: &str) -> Option<Self> {
+        let version = match version_str {
+            "HTTP/1.0" => Version::Http10,
+            "HTTP/1.1" => Version::Http11,
+            _ => return None,
+        };
+
+        Some(version)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        }
+    }
+
+    // HTTP/1.0 has no chunked transfer coding, so bodies of unknown length
+    // have to be delimited by closing the connection.
+    pub fn supports_chunked(&self) -> bool {
+        *self >= Version::Http11
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
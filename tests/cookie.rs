@@ -0,0 +1,197 @@
+use just_serve_it::cookie::{Cookie, CookieJar, Key, Keys, SameSite};
+use just_serve_it::header::Header;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn set_cookie_format() {
+    let cookie = Cookie::new("id", "abc")
+        .path("/")
+        .domain("example.com")
+        .max_age(Duration::from_secs(60))
+        .same_site(SameSite::Lax)
+        .secure()
+        .http_only();
+
+    assert_eq!(
+        cookie.to_string(),
+        "id=abc; Path=/; Domain=example.com; Max-Age=60; SameSite=Lax; Secure; HttpOnly"
+    );
+}
+
+#[test]
+fn values_cannot_inject() {
+    let cookie = Cookie::new("id", "x\r\nSet-Cookie: admin=1; Domain=evil");
+    let header = cookie.to_string();
+
+    assert!(!header.contains(['\r', '\n', ' ']));
+    assert_eq!(header.matches(';').count(), 0);
+    assert_eq!(header, "id=x%0D%0ASet-Cookie:%20admin=1%3B%20Domain=evil");
+}
+
+#[test]
+fn values_round_trip() {
+    let value = "a b;c,d\"e\\f%20g\u{e9}";
+    let header = Cookie::new("v", value).to_string();
+    let request = Header::from(vec![format!("Cookie: {header}; other=1")]);
+
+    assert_eq!(
+        request.cookie(),
+        [
+            ("v".to_string(), value.to_string()),
+            ("other".to_string(), "1".to_string())
+        ]
+    );
+}
+
+#[test]
+fn invalid_names_and_attributes() {
+    for name in ["", "a b", "a;b", "a=b", "a\r\nb", "caf\u{e9}", "a\"b"] {
+        let err = Cookie::new(name, "v").validate().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput, "{name:?}");
+    }
+
+    assert!(Cookie::new("a", "v")
+        .path("/; Domain=evil")
+        .validate()
+        .is_err());
+    assert!(Cookie::new("a", "v")
+        .domain("x\r\nX: y")
+        .validate()
+        .is_err());
+    assert!(Cookie::new("session_id-2", "v")
+        .path("/app")
+        .domain(".example.com")
+        .validate()
+        .is_ok());
+}
+
+fn key(byte: u8) -> Key {
+    Key::derive_from(&[byte; 32]).unwrap()
+}
+
+#[test]
+fn short_master_key() {
+    let err = Key::derive_from(&[0; 31]).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(Key::generate().is_ok());
+}
+
+#[test]
+fn signed_round_trip() {
+    let keys = Keys::new(key(1));
+    let signed = keys.sign("user", "alice");
+
+    assert!(signed.ends_with("alice"));
+    assert_eq!(keys.verify("user", &signed).as_deref(), Some("alice"));
+    assert_eq!(keys.sign("user", "").len(), 43);
+    assert_eq!(
+        keys.verify("user", &keys.sign("user", "")).as_deref(),
+        Some("")
+    );
+}
+
+#[test]
+fn signed_tampering() {
+    let keys = Keys::new(key(1));
+    let signed = keys.sign("user", "alice");
+
+    // Another value under the same signature.
+    assert_eq!(keys.verify("user", &signed.replace("alice", "admin")), None);
+    // The same value moved to another cookie.
+    assert_eq!(keys.verify("admin", &signed), None);
+    // A flipped signature character.
+    let mut flipped = signed.clone().into_bytes();
+    flipped[0] = if flipped[0] == b'A' { b'B' } else { b'A' };
+    assert_eq!(
+        keys.verify("user", &String::from_utf8(flipped).unwrap()),
+        None
+    );
+    // Signed with a key that isn't accepted.
+    assert_eq!(Keys::new(key(2)).verify("user", &signed), None);
+    // Too short or not base64 at all.
+    assert_eq!(keys.verify("user", "alice"), None);
+    assert_eq!(keys.verify("user", &"!".repeat(50)), None);
+    assert_eq!(keys.verify("user", "\u{e9}".repeat(30).as_str()), None);
+}
+
+#[test]
+fn private_round_trip() {
+    let keys = Keys::new(key(1));
+    let encrypted = keys.encrypt("token", "secret value").unwrap();
+
+    assert!(!encrypted.contains("secret"));
+    // Every encryption uses a fresh nonce.
+    assert_ne!(encrypted, keys.encrypt("token", "secret value").unwrap());
+    assert_eq!(
+        keys.decrypt("token", &encrypted).as_deref(),
+        Some("secret value")
+    );
+}
+
+#[test]
+fn private_tampering() {
+    let keys = Keys::new(key(1));
+    let encrypted = keys.encrypt("token", "secret").unwrap();
+
+    let mut bytes = encrypted.clone().into_bytes();
+    let last = bytes.len() - 1;
+    bytes[last] = if bytes[last] == b'A' { b'B' } else { b'A' };
+    assert_eq!(
+        keys.decrypt("token", &String::from_utf8(bytes).unwrap()),
+        None
+    );
+
+    assert_eq!(keys.decrypt("other", &encrypted), None);
+    assert_eq!(Keys::new(key(2)).decrypt("token", &encrypted), None);
+    assert_eq!(keys.decrypt("token", &encrypted[..10]), None);
+    assert_eq!(keys.decrypt("token", "not base64!"), None);
+}
+
+#[test]
+fn key_rotation() {
+    let old = Keys::new(key(1));
+    let signed = old.sign("user", "alice");
+    let encrypted = old.encrypt("token", "secret").unwrap();
+
+    let rotated = Keys::new(key(2)).accept(key(1));
+    assert_eq!(rotated.verify("user", &signed).as_deref(), Some("alice"));
+    assert_eq!(
+        rotated.decrypt("token", &encrypted).as_deref(),
+        Some("secret")
+    );
+
+    // New values use the current key only.
+    let resigned = rotated.sign("user", "alice");
+    assert_ne!(resigned, signed);
+    assert_eq!(old.verify("user", &resigned), None);
+    assert_eq!(
+        Keys::new(key(2)).verify("user", &resigned).as_deref(),
+        Some("alice")
+    );
+
+    // Dropping the old key stops accepting its values.
+    assert_eq!(Keys::new(key(2)).verify("user", &signed), None);
+}
+
+#[test]
+fn jar_reads_signed_and_private_cookies() {
+    let keys = Arc::new(Keys::new(key(1)));
+    let cookies = HashMap::from([
+        ("user".to_string(), keys.sign("user", "alice")),
+        (
+            "token".to_string(),
+            keys.encrypt("token", "secret").unwrap(),
+        ),
+        ("forged".to_string(), "x".repeat(48)),
+    ]);
+    let jar = CookieJar::new(cookies, Some(keys));
+
+    assert_eq!(jar.get_signed("user").as_deref(), Some("alice"));
+    assert_eq!(jar.get_private("token").as_deref(), Some("secret"));
+    assert_eq!(jar.get_signed("forged"), None);
+    assert_eq!(jar.get_private("user"), None);
+    assert_eq!(jar.get_signed("missing"), None);
+}
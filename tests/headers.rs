@@ -0,0 +1,231 @@
+use just_serve_it::header::typed::parse_http_date;
+use just_serve_it::header::{
+    Authorization, ByteRange, ContentDisposition, ContentType, EntityTag, Header, Host,
+    IfNoneMatch, QualityItem, Range,
+};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn content_type() {
+    let content_type = ContentType::parse("Text/HTML; Charset=\"utf-8\"; q=1").unwrap();
+    assert!(content_type.is("text/html"));
+    assert_eq!(content_type.charset(), Some("utf-8"));
+    assert_eq!(content_type.param("Q"), Some("1"));
+
+    let quoted = ContentType::parse("multipart/form-data; boundary=\"a;b\"").unwrap();
+    assert_eq!(quoted.param("boundary"), Some("a;b"));
+
+    assert_eq!(ContentType::parse(""), None);
+    assert_eq!(ContentType::parse("text"), None);
+    assert_eq!(ContentType::parse("text/"), None);
+    assert_eq!(ContentType::parse("/html"), None);
+}
+
+#[test]
+fn quality_values() {
+    let items = QualityItem::parse_list("text/html;q=0.5, application/json, */*;q=0.1");
+    let values: Vec<_> = items.iter().map(|item| item.value.as_str()).collect();
+    assert_eq!(values, ["application/json", "text/html", "*/*"]);
+    assert_eq!(items[1].q, 0.5);
+
+    // Equal weights keep the client's order.
+    let items = QualityItem::parse_list("b, a, c;q=1");
+    let values: Vec<_> = items.iter().map(|item| item.value.as_str()).collect();
+    assert_eq!(values, ["b", "a", "c"]);
+
+    // Out of range or unparsable weights drop the entry.
+    let items = QualityItem::parse_list("a;q=2, b;q=x, c;q=-1, , d");
+    let values: Vec<_> = items.iter().map(|item| item.value.as_str()).collect();
+    assert_eq!(values, ["d"]);
+
+    assert!(QualityItem::parse_list("").is_empty());
+}
+
+#[test]
+fn media_ranges() {
+    let items = QualityItem::parse_list("text/*, */*, image/png");
+    assert!(items[0].matches("TEXT/plain"));
+    assert!(!items[0].matches("image/png"));
+    assert!(items[1].matches("anything/else"));
+    assert!(items[2].matches("image/png"));
+    assert!(!items[2].matches("image/gif"));
+}
+
+#[test]
+fn encoding_negotiation() {
+    let offers = ["gzip", "deflate"];
+    let negotiate =
+        |value: &str| QualityItem::negotiate_encoding(&QualityItem::parse_list(value), &offers);
+
+    assert_eq!(negotiate("deflate, gzip"), Some("gzip"));
+    assert_eq!(negotiate("gzip;q=0.5, deflate"), Some("deflate"));
+    assert_eq!(negotiate("x-gzip"), Some("gzip"));
+    assert_eq!(negotiate("br, *;q=0.2"), Some("gzip"));
+    assert_eq!(negotiate("*, gzip;q=0"), Some("deflate"));
+    assert_eq!(negotiate("br"), None);
+    assert_eq!(negotiate("*;q=0"), None);
+    assert_eq!(negotiate(""), None);
+}
+
+#[test]
+fn authorization() {
+    // "user:pass:word", the password may contain colons.
+    let basic = Authorization::parse("basic dXNlcjpwYXNzOndvcmQ=").unwrap();
+    assert!(basic.is_scheme("Basic"));
+    assert_eq!(
+        basic.basic(),
+        Some(("user".to_string(), "pass:word".to_string()))
+    );
+    assert_eq!(basic.bearer(), None);
+
+    let bearer = Authorization::parse("Bearer   token.value ").unwrap();
+    assert_eq!(bearer.bearer(), Some("token.value"));
+    assert_eq!(bearer.basic(), None);
+
+    assert_eq!(Authorization::parse(""), None);
+    assert_eq!(Authorization::parse("Bearer").unwrap().bearer(), None);
+    assert_eq!(Authorization::parse("Basic !!!").unwrap().basic(), None);
+    // Valid base64 without a colon.
+    assert_eq!(
+        Authorization::parse("Basic dXNlcg==").unwrap().basic(),
+        None
+    );
+}
+
+#[test]
+fn entity_tags() {
+    assert_eq!(EntityTag::parse("\"abc\""), Some(EntityTag::strong("abc")));
+    assert_eq!(
+        EntityTag::parse(" W/\"abc\" "),
+        Some(EntityTag::weak("abc"))
+    );
+    assert_eq!(EntityTag::parse("abc"), None);
+    assert_eq!(EntityTag::parse("\"a\"b\""), None);
+    assert_eq!(EntityTag::parse("w/\"abc\""), None);
+    assert_eq!(EntityTag::weak("x").to_string(), "W/\"x\"");
+
+    let tags = IfNoneMatch::parse("\"a\", W/\"b\"").unwrap();
+    assert!(tags.matches(&EntityTag::strong("b")));
+    assert!(!tags.matches_strong(&EntityTag::strong("b")));
+    assert!(tags.matches_strong(&EntityTag::strong("a")));
+    assert_eq!(IfNoneMatch::parse("*"), Some(IfNoneMatch::Any));
+    assert_eq!(IfNoneMatch::parse("\"a\", b"), None);
+}
+
+#[test]
+fn ranges() {
+    let range = Range::parse("Bytes=0-99, 200-, -50").unwrap();
+    assert!(range.is_bytes());
+    assert_eq!(
+        range.ranges,
+        [
+            ByteRange::FromTo(0, 99),
+            ByteRange::From(200),
+            ByteRange::Last(50)
+        ]
+    );
+    assert!(!Range::parse("items=0-1").unwrap().is_bytes());
+
+    for malformed in [
+        "",
+        "bytes",
+        "bytes=",
+        "bytes=-",
+        "bytes=5-2",
+        "bytes=a-b",
+        "bytes=0-1,",
+    ] {
+        assert_eq!(Range::parse(malformed), None, "{malformed:?}");
+    }
+}
+
+#[test]
+fn range_bounds() {
+    assert_eq!(ByteRange::FromTo(0, 999).to_bounds(100), Some((0, 99)));
+    assert_eq!(ByteRange::FromTo(100, 200).to_bounds(100), None);
+    assert_eq!(ByteRange::From(10).to_bounds(100), Some((10, 99)));
+    assert_eq!(ByteRange::From(100).to_bounds(100), None);
+    assert_eq!(ByteRange::Last(10).to_bounds(100), Some((90, 99)));
+    assert_eq!(ByteRange::Last(500).to_bounds(100), Some((0, 99)));
+    assert_eq!(ByteRange::Last(0).to_bounds(100), None);
+    assert_eq!(ByteRange::From(0).to_bounds(0), None);
+}
+
+#[test]
+fn host() {
+    let host = Host::parse("Example.COM:8080").unwrap();
+    assert_eq!(host.name, "example.com");
+    assert_eq!(host.port, Some(8080));
+
+    let host = Host::parse("[::1]:3000").unwrap();
+    assert_eq!(host.name, "[::1]");
+    assert_eq!(host.port, Some(3000));
+    assert_eq!(Host::parse("[::1]").unwrap().port, None);
+    assert_eq!(Host::parse("localhost").unwrap().port, None);
+
+    for malformed in [
+        "",
+        ":80",
+        "host:port",
+        "host:99999",
+        "[::1",
+        "[::1]x",
+        "[::1]:",
+        "a:b:c",
+    ] {
+        assert_eq!(Host::parse(malformed), None, "{malformed:?}");
+    }
+}
+
+#[test]
+fn content_disposition() {
+    let disposition =
+        ContentDisposition::parse("form-data; name=\"file\"; filename=\"a; b.txt\"").unwrap();
+    assert_eq!(disposition.kind, "form-data");
+    assert_eq!(disposition.name(), Some("file"));
+    assert_eq!(disposition.filename().as_deref(), Some("a; b.txt"));
+
+    let extended = ContentDisposition::parse(
+        "attachment; filename=\"fallback.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9.txt",
+    )
+    .unwrap();
+    assert_eq!(extended.filename().as_deref(), Some("résumé.txt"));
+
+    // Unknown charsets fall back to the plain parameter.
+    let latin1 =
+        ContentDisposition::parse("attachment; filename=plain.txt; filename*=ISO-8859-1''x")
+            .unwrap();
+    assert_eq!(latin1.filename().as_deref(), Some("plain.txt"));
+
+    assert_eq!(ContentDisposition::parse(""), None);
+    assert_eq!(ContentDisposition::parse("name=\"x\""), None);
+}
+
+#[test]
+fn http_dates() {
+    let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    assert_eq!(date, UNIX_EPOCH + Duration::from_secs(784111777));
+    assert_eq!(parse_http_date("yesterday"), None);
+}
+
+#[test]
+fn header_lookup() {
+    let headers = Header::from(vec![
+        "content-type: application/json".to_string(),
+        "X-Empty:".to_string(),
+        "no colon here".to_string(),
+        "Cookie: a=1; b=\"2\"".to_string(),
+    ]);
+
+    assert!(headers.content_type().unwrap().is("application/json"));
+    assert_eq!(headers.get("x-empty").map(String::as_str), Some(""));
+    assert_eq!(headers.fields.len(), 3);
+    assert_eq!(
+        headers.cookie(),
+        [
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "2".to_string())
+        ]
+    );
+    assert_eq!(headers.content_length(), None);
+}
@@ -0,0 +1,60 @@
+use just_serve_it::mime::{self, MimeTypes};
+
+#[test]
+fn sniff_signatures() {
+    assert_eq!(mime::sniff(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+    assert_eq!(mime::sniff(b"%PDF-1.7"), Some("application/pdf"));
+    assert_eq!(mime::sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+    assert_eq!(mime::sniff(b"\0\0\0\x18ftypmp42"), Some("video/mp4"));
+}
+
+#[test]
+fn sniff_text() {
+    assert_eq!(mime::sniff(b"  <!DOCTYPE html><p>hi"), Some("text/html"));
+    assert_eq!(mime::sniff(b"<svg xmlns="), Some("image/svg+xml"));
+    assert_eq!(mime::sniff(b"plain words"), Some("text/plain"));
+    assert_eq!(mime::sniff(b"nul\0inside"), None);
+    assert_eq!(mime::sniff(b"\xff\xfe\xfd not utf-8"), None);
+}
+
+#[test]
+fn sniff_truncated_utf8() {
+    // A head cut off in the middle of a multibyte character is still text.
+    let text = "caf\u{e9} \u{1f600}".as_bytes();
+    for cut in text.len() - 3..text.len() {
+        assert_eq!(mime::sniff(&text[..cut]), Some("text/plain"), "{cut}");
+    }
+
+    // An invalid sequence before the end is not.
+    assert_eq!(mime::sniff(b"ab\xc3(cd"), None);
+}
+
+#[test]
+fn sniff_empty() {
+    assert_eq!(mime::sniff(b""), None);
+
+    let mut types = MimeTypes::new();
+    types.set_sniffing(true);
+    assert_eq!(types.for_path("empty", Some(b"")), mime::DEFAULT);
+}
+
+#[test]
+fn types_for_paths() {
+    let mut types = MimeTypes::new();
+    assert_eq!(types.for_path("a/b.HTML", None), "text/html; charset=utf-8");
+    assert_eq!(types.for_path("photo.jpg", None), "image/jpeg");
+    assert_eq!(types.for_path("README", Some(b"text")), mime::DEFAULT);
+
+    types.register(".jpg", "image/x-custom");
+    types.set_sniffing(true);
+    assert_eq!(types.for_path("photo.JPG", None), "image/x-custom");
+    assert_eq!(
+        types.for_path("README", Some(b"text")),
+        "text/plain; charset=utf-8"
+    );
+    // Known extensions win over the content.
+    assert_eq!(
+        types.for_path("data.json", Some(b"\x89PNG\r\n\x1a\n")),
+        "application/json; charset=utf-8"
+    );
+}